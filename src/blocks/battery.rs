@@ -0,0 +1,564 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::mem;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::{line, matches, read_file, Block};
+use crate::error::Result;
+use crate::event::Notifier;
+use crate::format::{self, Template, Value};
+use crate::protocol::StatusLine;
+use crate::threshold::{Direction, State, Thresholds};
+
+pub const PLACEHOLDERS: &[&str] = &[
+    "icon", "percent", "status", "time", "power", "health", "name",
+];
+
+#[derive(Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Options {
+    /// Pattern (`*` and `?` wildcards) of the batteries to show, e.g. `BAT0`.
+    device: Option<String>,
+    /// Whether to show every battery on its own instead of adding them up.
+    split: bool,
+    /// Where sysfs is mounted.
+    sysfs: PathBuf,
+    format: Template,
+    short_format: Option<Template>,
+    thresholds: Thresholds,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            device: None,
+            split: false,
+            sysfs: PathBuf::from("/sys"),
+            format: Template::parse("{icon} :  {percent:>5.1} % {time}").unwrap(),
+            short_format: None,
+            thresholds: Thresholds::new(Some(80.0), 30.0, 10.0),
+        }
+    }
+}
+
+/// What a battery is doing, as found in its `status` file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Status {
+    Charging,
+    Discharging,
+    Full,
+    NotCharging,
+    Unknown,
+}
+
+impl Status {
+    fn parse(status: &str) -> Status {
+        match status {
+            "Charging" => Status::Charging,
+            "Discharging" => Status::Discharging,
+            "Full" => Status::Full,
+            "Not charging" => Status::NotCharging,
+            _ => Status::Unknown,
+        }
+    }
+
+    fn text(self) -> &'static str {
+        match self {
+            Status::Charging => "charging",
+            Status::Discharging => "discharging",
+            Status::Full => "full",
+            Status::NotCharging => "not charging",
+            Status::Unknown => "unknown",
+        }
+    }
+}
+
+/// A battery, or several added up. Energies are in watt-hours and power in
+/// watts, any of them may be missing depending on the driver.
+struct Reading {
+    name: String,
+    status: Status,
+    /// Charge level reported by the driver, used when energies are missing.
+    capacity: Option<f64>,
+    energy_now: Option<f64>,
+    energy_full: Option<f64>,
+    energy_full_design: Option<f64>,
+    power: Option<f64>,
+    state: State,
+}
+
+impl Reading {
+    /// Reads `<sysfs>/class/power_supply/<name>`. Drivers give either energies
+    /// in µWh and power in µW, or charges in µAh and current in µA along with
+    /// the voltage in µV.
+    fn read(path: &Path, name: String) -> Reading {
+        let value = |file: &str| -> Option<f64> { read_file(&path.join(file))?.parse().ok() };
+        let voltage = value("voltage_now").map(|uv| uv / 1e6);
+        let energy = |energy: &str, charge: &str| match value(energy) {
+            Some(uwh) => Some(uwh / 1e6),
+            None => Some(value(charge)? / 1e6 * voltage?),
+        };
+        let power = match value("power_now") {
+            Some(uw) => Some(uw / 1e6),
+            None => value("current_now")
+                .zip(voltage)
+                .map(|(ua, v)| ua / 1e6 * v),
+        };
+        Reading {
+            name,
+            status: Status::parse(&read_file(&path.join("status")).unwrap_or_default()),
+            capacity: value("capacity"),
+            energy_now: energy("energy_now", "charge_now"),
+            energy_full: energy("energy_full", "charge_full"),
+            energy_full_design: energy("energy_full_design", "charge_full_design"),
+            power: power.map(f64::abs),
+            state: State::Idle,
+        }
+    }
+
+    /// Adds up several batteries into one.
+    fn combine(readings: Vec<Reading>) -> Reading {
+        let sum = |field: fn(&Reading) -> Option<f64>| -> Option<f64> {
+            readings.iter().map(field).sum()
+        };
+        let statuses: Vec<Status> = readings.iter().map(|reading| reading.status).collect();
+        let status = if statuses.contains(&Status::Charging) {
+            Status::Charging
+        } else if statuses.contains(&Status::Discharging) {
+            Status::Discharging
+        } else if statuses.iter().all(|&status| status == Status::Full) {
+            Status::Full
+        } else {
+            statuses.first().copied().unwrap_or(Status::Unknown)
+        };
+        let capacities: Vec<f64> = readings.iter().filter_map(|r| r.capacity).collect();
+        Reading {
+            name: readings
+                .iter()
+                .map(|reading| reading.name.as_str())
+                .collect::<Vec<_>>()
+                .join("+"),
+            status,
+            capacity: match capacities.len() {
+                0 => None,
+                len => Some(capacities.iter().sum::<f64>() / len as f64),
+            },
+            energy_now: sum(|r| r.energy_now),
+            energy_full: sum(|r| r.energy_full),
+            energy_full_design: sum(|r| r.energy_full_design),
+            power: sum(|r| r.power),
+            state: State::Idle,
+        }
+    }
+
+    fn percent(&self) -> f64 {
+        match (self.energy_now, self.energy_full) {
+            (Some(now), Some(full)) if full > 0.0 => (now / full * 100.0).min(100.0),
+            _ => self.capacity.unwrap_or(0.0),
+        }
+    }
+
+    /// Share of the design capacity the battery can still hold.
+    fn health(&self) -> Option<f64> {
+        match (self.energy_full, self.energy_full_design) {
+            (Some(full), Some(design)) if design > 0.0 => Some(full / design * 100.0),
+            _ => None,
+        }
+    }
+
+    /// Seconds until the battery is empty or full, at the current power.
+    fn time_remaining(&self) -> Option<u64> {
+        let power = self.power.filter(|&power| power > 0.0)?;
+        let energy = match self.status {
+            Status::Discharging => self.energy_now?,
+            Status::Charging => self.energy_full? - self.energy_now?,
+            _ => return None,
+        };
+        Some((energy.max(0.0) / power * 3600.0) as u64)
+    }
+}
+
+/// Names of the batteries in `<sysfs>/class/power_supply`, sorted.
+fn battery_names(sysfs: &Path) -> Vec<String> {
+    let mut names: Vec<String> = match fs::read_dir(sysfs.join("class/power_supply")) {
+        Ok(supplies) => supplies
+            .flatten()
+            .filter(|supply| read_file(&supply.path().join("type")).as_deref() == Some("Battery"))
+            .filter_map(|supply| supply.file_name().into_string().ok())
+            .collect(),
+        Err(_) => vec![],
+    };
+    names.sort();
+    names
+}
+
+/// Whether an AC adapter is plugged in, `None` without any adapter.
+fn on_ac_power(sysfs: &Path) -> Option<bool> {
+    let supplies = fs::read_dir(sysfs.join("class/power_supply")).ok()?;
+    let mut online = None;
+    for supply in supplies.flatten() {
+        let path = supply.path();
+        if read_file(&path.join("type")).as_deref() == Some("Mains") {
+            let plugged = read_file(&path.join("online")).as_deref() == Some("1");
+            online = Some(online.unwrap_or(false) || plugged);
+        }
+    }
+    online
+}
+
+pub struct Battery {
+    readings: Vec<Reading>,
+    on_ac: bool,
+    options: Options,
+}
+
+impl Battery {
+    pub fn new(options: Options) -> Result<Self, toml::de::Error> {
+        Ok(Battery {
+            readings: vec![],
+            on_ac: false,
+            options,
+        })
+    }
+}
+
+impl Block for Battery {
+    fn name(&self) -> &'static str {
+        "battery"
+    }
+
+    fn color(&self) -> &'static str {
+        "green"
+    }
+
+    /// Watches the AC adapter so plugging or unplugging shows up right away.
+    fn start(&mut self, notifier: Notifier) {
+        let sysfs = self.options.sysfs.clone();
+        thread::spawn(move || {
+            // Only the real sysfs gets uevents.
+            if sysfs == Path::new("/sys") {
+                match Uevents::open() {
+                    Ok(uevents) => match watch_uevents(&uevents, &notifier) {
+                        Ok(()) => return,
+                        Err(err) => log::debug!("cannot read uevents, polling instead: {}", err),
+                    },
+                    Err(err) => log::debug!("cannot watch uevents, polling instead: {}", err),
+                }
+            }
+            poll_ac_power(&sysfs, &notifier);
+        });
+    }
+
+    fn update(&mut self) -> Result<()> {
+        let previous: HashMap<String, State> = self
+            .readings
+            .drain(..)
+            .map(|reading| (reading.name, reading.state))
+            .collect();
+
+        let supplies = self.options.sysfs.join("class/power_supply");
+        let mut readings: Vec<Reading> = battery_names(&self.options.sysfs)
+            .into_iter()
+            .filter(|name| {
+                let device = self.options.device.as_deref();
+                device.is_none_or(|device| matches(device, name))
+            })
+            .map(|name| Reading::read(&supplies.join(&name), name))
+            .collect();
+        if !self.options.split && readings.len() > 1 {
+            readings = vec![Reading::combine(readings)];
+        }
+        for reading in &mut readings {
+            let previous = previous.get(&reading.name).copied();
+            reading.state = self.options.thresholds.state(
+                reading.percent(),
+                previous.unwrap_or(State::Idle),
+                Direction::LowerIsWorse,
+            );
+        }
+        self.readings = readings;
+
+        // Without an adapter to ask, trust the batteries.
+        self.on_ac = on_ac_power(&self.options.sysfs).unwrap_or_else(|| {
+            self.readings
+                .iter()
+                .any(|reading| matches!(reading.status, Status::Charging | Status::Full))
+        });
+        Ok(())
+    }
+
+    fn render(&self) -> Vec<StatusLine> {
+        self.readings
+            .iter()
+            .map(|reading| {
+                let percent = reading.percent();
+                let values = [
+                    (
+                        "icon",
+                        Value::Text(battery_icon(percent as f32 / 100.0, self.on_ac).to_string()),
+                    ),
+                    ("percent", Value::Number(percent)),
+                    ("status", Value::Text(reading.status.text().to_string())),
+                    (
+                        "time",
+                        Value::Text(
+                            reading
+                                .time_remaining()
+                                .map(format::duration)
+                                .unwrap_or_default(),
+                        ),
+                    ),
+                    ("power", Value::Number(reading.power.unwrap_or(0.0))),
+                    ("health", Value::Number(reading.health().unwrap_or(100.0))),
+                    ("name", Value::Text(reading.name.clone())),
+                ];
+
+                StatusLine {
+                    instance: Some(reading.name.clone()),
+                    state: match (self.on_ac, reading.state) {
+                        // Charging is worth noticing unless a threshold says more.
+                        (true, State::Idle) => State::Info,
+                        (_, state) => state,
+                    },
+                    ..line(
+                        &self.options.format,
+                        self.options.short_format.as_ref(),
+                        &values,
+                    )
+                }
+            })
+            .collect()
+    }
+}
+
+/// Notifies the block of every change of a power supply, until the block is
+/// no longer part of the bar.
+fn watch_uevents(uevents: &Uevents, notifier: &Notifier) -> io::Result<()> {
+    while notifier.is_active() {
+        if uevents.wait()? && !notifier.notify() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Fallback of `watch_uevents`, e.g. in containers or against a fake sysfs:
+/// reads the AC adapter twice a second while the bar is shown.
+fn poll_ac_power(sysfs: &Path, notifier: &Notifier) {
+    let mut on_ac = on_ac_power(sysfs);
+    while notifier.is_active() {
+        thread::sleep(POLL_INTERVAL);
+        if notifier.is_paused() {
+            continue;
+        }
+        let now_on_ac = on_ac_power(sysfs);
+        if now_on_ac != on_ac {
+            on_ac = now_on_ac;
+            if !notifier.notify() {
+                break;
+            }
+        }
+    }
+}
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The netlink socket the kernel sends its uevents to, udev listening to it
+/// as well.
+struct Uevents {
+    fd: OwnedFd,
+}
+
+impl Uevents {
+    /// Multicast group of the uevents sent by the kernel itself.
+    const KERNEL_GROUP: u32 = 1;
+    /// How long `wait` blocks at most, so a block no longer in the bar gets
+    /// its thread back.
+    const TIMEOUT: libc::timeval = libc::timeval {
+        tv_sec: 1,
+        tv_usec: 0,
+    };
+
+    fn open() -> io::Result<Uevents> {
+        // SAFETY: plain system calls, the file descriptor is owned right after
+        // being checked and the addresses passed are valid for their sizes.
+        unsafe {
+            let fd = libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_DGRAM | libc::SOCK_CLOEXEC,
+                libc::NETLINK_KOBJECT_UEVENT,
+            );
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let fd = OwnedFd::from_raw_fd(fd);
+            let mut address: libc::sockaddr_nl = mem::zeroed();
+            address.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+            address.nl_groups = Self::KERNEL_GROUP;
+            let bound = libc::bind(
+                fd.as_raw_fd(),
+                &address as *const libc::sockaddr_nl as *const libc::sockaddr,
+                mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            );
+            let timed_out = libc::setsockopt(
+                fd.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_RCVTIMEO,
+                &Self::TIMEOUT as *const libc::timeval as *const libc::c_void,
+                mem::size_of::<libc::timeval>() as libc::socklen_t,
+            );
+            if bound < 0 || timed_out < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Uevents { fd })
+        }
+    }
+
+    /// Waits for the next uevent, returning whether it is about a power
+    /// supply. Also returns `false` after a while without any uevent.
+    fn wait(&self) -> io::Result<bool> {
+        let mut buffer = [0u8; 8192];
+        // SAFETY: the buffer is valid for writes of its whole length.
+        let len = unsafe {
+            libc::recv(
+                self.fd.as_raw_fd(),
+                buffer.as_mut_ptr() as *mut libc::c_void,
+                buffer.len(),
+                0,
+            )
+        };
+        if len < 0 {
+            let err = io::Error::last_os_error();
+            return match err.kind() {
+                io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted => Ok(false),
+                _ => Err(err),
+            };
+        }
+        // `action@devpath` followed by `KEY=value` fields, all NUL-terminated.
+        Ok(buffer[..len as usize]
+            .split(|&byte| byte == 0)
+            .any(|field| field == b"SUBSYSTEM=power_supply"))
+    }
+}
+
+fn battery_icon(capacity: f32, on_ac: bool) -> char {
+    if on_ac {
+        ''
+    } else {
+        let step = (capacity * 10.0) as i8;
+        match step {
+            9 => '',
+            8 => '',
+            7 => '',
+            6 => '',
+            5 => '',
+            4 => '',
+            3 => '',
+            2 => '',
+            1 => '',
+            0 => '',
+            _ => '',
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::fake_sysfs::FakeSysfs;
+
+    /// Two batteries discharging at 10 W each: `BAT0` reporting energies and
+    /// `BAT1` charges, both half full.
+    fn sysfs(name: &str) -> FakeSysfs {
+        let sysfs = FakeSysfs::new(name);
+        sysfs
+            .write("class/power_supply/AC/type", "Mains")
+            .write("class/power_supply/AC/online", "0")
+            .write("class/power_supply/BAT0/type", "Battery")
+            .write("class/power_supply/BAT0/status", "Discharging")
+            .write("class/power_supply/BAT0/energy_now", "30000000")
+            .write("class/power_supply/BAT0/energy_full", "60000000")
+            .write("class/power_supply/BAT0/energy_full_design", "80000000")
+            .write("class/power_supply/BAT0/power_now", "10000000")
+            .write("class/power_supply/BAT1/type", "Battery")
+            .write("class/power_supply/BAT1/status", "Discharging")
+            .write("class/power_supply/BAT1/voltage_now", "10000000")
+            .write("class/power_supply/BAT1/charge_now", "2000000")
+            .write("class/power_supply/BAT1/charge_full", "4000000")
+            .write("class/power_supply/BAT1/charge_full_design", "4000000")
+            .write("class/power_supply/BAT1/current_now", "1000000");
+        sysfs
+    }
+
+    /// Updates a block made of `options` and returns its lines.
+    fn render(sysfs: &FakeSysfs, options: &str) -> Vec<StatusLine> {
+        let options = format!(
+            "sysfs = {:?}\nformat = \"{{name}} {{percent:.0}} {{status}} {{time}} {{power:.0}} {{health:.0}}\"\n{}",
+            sysfs.path(),
+            options
+        );
+        let mut block = Battery::new(toml::from_str(&options).unwrap()).unwrap();
+        block.update().unwrap();
+        block.render()
+    }
+
+    fn texts(lines: &[StatusLine]) -> Vec<&str> {
+        lines.iter().map(|line| line.full_text.as_str()).collect()
+    }
+
+    #[test]
+    fn reads_energies_and_charges() {
+        let sysfs = sysfs("battery-read");
+        let lines = render(&sysfs, "device = \"BAT0\"");
+        assert_eq!(texts(&lines), ["BAT0 50 discharging 3h 10 75"]);
+        let lines = render(&sysfs, "device = \"BAT1\"");
+        assert_eq!(texts(&lines), ["BAT1 50 discharging 2h 10 100"]);
+    }
+
+    #[test]
+    fn adds_up_batteries_unless_split() {
+        let sysfs = sysfs("battery-combine");
+        let lines = render(&sysfs, "");
+        assert_eq!(texts(&lines), ["BAT0+BAT1 50 discharging 2h 30m 20 83"]);
+
+        let lines = render(&sysfs, "split = true");
+        assert_eq!(texts(&lines).len(), 2);
+        assert_eq!(lines[0].instance.as_deref(), Some("BAT0"));
+        assert_eq!(lines[1].instance.as_deref(), Some("BAT1"));
+    }
+
+    #[test]
+    fn gets_worse_as_the_charge_goes_down() {
+        let sysfs = sysfs("battery-thresholds");
+        let lines = render(&sysfs, "[thresholds]\ncritical = 10");
+        assert_eq!(lines[0].state, State::Idle);
+
+        sysfs.write("class/power_supply/BAT0/energy_now", "3000000");
+        let lines = render(&sysfs, "device = \"BAT0\"\n[thresholds]\ncritical = 10");
+        assert_eq!(lines[0].state, State::Critical);
+    }
+
+    #[test]
+    fn shows_when_plugged_in() {
+        let sysfs = sysfs("battery-ac");
+        sysfs.write("class/power_supply/AC/online", "1");
+        let lines = render(&sysfs, "device = \"BAT0\"");
+        assert_eq!(lines[0].state, State::Info);
+        assert!(lines[0].full_text.starts_with("BAT0"));
+    }
+
+    #[test]
+    fn renders_nothing_without_a_battery() {
+        let sysfs = FakeSysfs::new("battery-none");
+        sysfs
+            .write("class/power_supply/AC/type", "Mains")
+            .write("class/power_supply/AC/online", "1");
+        assert!(render(&sysfs, "").is_empty());
+    }
+}
@@ -0,0 +1,276 @@
+use std::collections::VecDeque;
+use std::fs;
+
+use serde::de::Error as _;
+use serde::{Deserialize, Serialize};
+
+use super::{line, Block};
+use crate::error::{Error, Result};
+use crate::format::{self, Template, Value};
+use crate::protocol::StatusLine;
+use crate::threshold::{Direction, State, Thresholds};
+
+/// Largest `history`, already wider than any bar.
+const MAX_HISTORY: usize = 1000;
+
+pub const PLACEHOLDERS: &[&str] = &[
+    "usage",
+    "user",
+    "system",
+    "iowait",
+    "steal",
+    "cores",
+    "frequency",
+    "history",
+];
+
+#[derive(Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Options {
+    format: Template,
+    short_format: Option<Template>,
+    thresholds: Thresholds,
+    /// Number of samples of the global usage kept for `{history}`.
+    history: usize,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            format: Template::parse(" : {usage:>5.1} %").unwrap(),
+            short_format: None,
+            thresholds: Thresholds::new(None, 80.0, 95.0),
+            history: 10,
+        }
+    }
+}
+
+/// Time spent by a CPU in each mode since boot, in ticks, as found in
+/// `/proc/stat`.
+#[derive(Debug, Clone, Copy, Default)]
+struct Times {
+    user: u64,
+    nice: u64,
+    system: u64,
+    idle: u64,
+    iowait: u64,
+    irq: u64,
+    softirq: u64,
+    steal: u64,
+}
+
+impl Times {
+    fn parse(fields: &[&str]) -> Times {
+        let field = |i: usize| fields.get(i).and_then(|f| f.parse().ok()).unwrap_or(0);
+        Times {
+            user: field(0),
+            nice: field(1),
+            system: field(2),
+            idle: field(3),
+            iowait: field(4),
+            irq: field(5),
+            softirq: field(6),
+            steal: field(7),
+        }
+    }
+
+    /// Guest time is already accounted for in user time.
+    fn total(&self) -> u64 {
+        self.user
+            + self.nice
+            + self.system
+            + self.idle
+            + self.iowait
+            + self.irq
+            + self.softirq
+            + self.steal
+    }
+
+    /// Time spent since `previous`.
+    fn since(&self, previous: &Times) -> Times {
+        Times {
+            user: self.user.saturating_sub(previous.user),
+            nice: self.nice.saturating_sub(previous.nice),
+            system: self.system.saturating_sub(previous.system),
+            idle: self.idle.saturating_sub(previous.idle),
+            iowait: self.iowait.saturating_sub(previous.iowait),
+            irq: self.irq.saturating_sub(previous.irq),
+            softirq: self.softirq.saturating_sub(previous.softirq),
+            steal: self.steal.saturating_sub(previous.steal),
+        }
+    }
+
+    /// Share of the total time spent in `ticks`, in percent.
+    fn percent(&self, ticks: u64) -> f32 {
+        match self.total() {
+            0 => 0.0,
+            total => ticks as f32 / total as f32 * 100.0,
+        }
+    }
+
+    /// Share of the time not spent idle or waiting for I/O.
+    fn usage(&self) -> f32 {
+        100.0 - self.percent(self.idle + self.iowait)
+    }
+}
+
+const STAT: &str = "/proc/stat";
+
+/// Reads the times of all CPUs together, then of each core.
+fn read_times() -> Result<(Times, Vec<Times>)> {
+    let stat = fs::read_to_string(STAT).map_err(|err| Error::Read(STAT.into(), err))?;
+    let mut global = Times::default();
+    let mut cores = vec![];
+    for line in stat.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        match fields.first() {
+            Some(&"cpu") => global = Times::parse(&fields[1..]),
+            Some(name) if name.starts_with("cpu") => cores.push(Times::parse(&fields[1..])),
+            _ => {}
+        }
+    }
+    Ok((global, cores))
+}
+
+/// Average frequency of the cores, in MHz.
+fn read_frequency() -> u64 {
+    let cpuinfo = match fs::read_to_string("/proc/cpuinfo") {
+        Ok(cpuinfo) => cpuinfo,
+        Err(_) => return 0,
+    };
+    let frequencies: Vec<f64> = cpuinfo
+        .lines()
+        .filter(|line| line.starts_with("cpu MHz"))
+        .filter_map(|line| line.split(':').nth(1)?.trim().parse().ok())
+        .collect();
+    match frequencies.len() {
+        0 => 0,
+        len => (frequencies.iter().sum::<f64>() / len as f64) as u64,
+    }
+}
+
+pub struct Cpu {
+    /// Times read on the previous update, usages are measured since then.
+    previous: (Times, Vec<Times>),
+    /// Time spent in each mode since the previous update.
+    window: Times,
+    load: f32,
+    /// Usage of each core.
+    cores: Vec<f32>,
+    /// Average frequency of the cores, in MHz.
+    frequency: u64,
+    /// Last samples of the global usage, oldest first.
+    history: VecDeque<f32>,
+    state: State,
+    options: Options,
+}
+
+impl Cpu {
+    pub fn new(options: Options) -> Result<Self, toml::de::Error> {
+        if options.history > MAX_HISTORY {
+            return Err(toml::de::Error::custom(format!(
+                "`history` cannot be above {}",
+                MAX_HISTORY
+            )));
+        }
+        Ok(Cpu {
+            // The first update measures usage since the block was created.
+            previous: read_times().unwrap_or_default(),
+            window: Times::default(),
+            load: 0.0,
+            cores: vec![],
+            frequency: 0,
+            history: VecDeque::new(),
+            state: State::Idle,
+            options,
+        })
+    }
+}
+
+impl Block for Cpu {
+    fn name(&self) -> &'static str {
+        "cpu"
+    }
+
+    fn color(&self) -> &'static str {
+        "green"
+    }
+
+    fn update(&mut self) -> Result<()> {
+        let (global, cores) = read_times()?;
+        let window = global.since(&self.previous.0);
+        // No tick elapsed yet, wait for a window long enough to measure.
+        if window.total() == 0 {
+            return Ok(());
+        }
+        self.window = window;
+        self.load = self.window.usage();
+        self.cores = cores
+            .iter()
+            .enumerate()
+            .map(|(i, core)| match self.previous.1.get(i) {
+                Some(previous) => core.since(previous).usage(),
+                None => 0.0,
+            })
+            .collect();
+        self.previous = (global, cores);
+        self.frequency = read_frequency();
+
+        if self.options.history > 0 {
+            if self.history.len() == self.options.history {
+                self.history.pop_front();
+            }
+            self.history.push_back(self.load);
+        }
+        self.state =
+            self.options
+                .thresholds
+                .state(self.load as f64, self.state, Direction::HigherIsWorse);
+        Ok(())
+    }
+
+    fn render(&self) -> Vec<StatusLine> {
+        let values = [
+            ("usage", Value::Number(self.load as f64)),
+            (
+                "user",
+                Value::Number(self.window.percent(self.window.user + self.window.nice) as f64),
+            ),
+            (
+                "system",
+                Value::Number(
+                    self.window
+                        .percent(self.window.system + self.window.irq + self.window.softirq)
+                        as f64,
+                ),
+            ),
+            (
+                "iowait",
+                Value::Number(self.window.percent(self.window.iowait) as f64),
+            ),
+            (
+                "steal",
+                Value::Number(self.window.percent(self.window.steal) as f64),
+            ),
+            (
+                "cores",
+                Value::Text(format::sparkline(self.cores.iter().map(|&c| c as f64))),
+            ),
+            // In GHz.
+            ("frequency", Value::Number(self.frequency as f64 / 1000.0)),
+            (
+                "history",
+                Value::Text(format::sparkline(self.history.iter().map(|&h| h as f64))),
+            ),
+        ];
+
+        vec![StatusLine {
+            state: self.state,
+            ..line(
+                &self.options.format,
+                self.options.short_format.as_ref(),
+                &values,
+            )
+        }]
+    }
+}
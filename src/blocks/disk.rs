@@ -0,0 +1,489 @@
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::fs;
+use std::io;
+use std::mem::MaybeUninit;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::thread;
+
+use serde::de::Error as _;
+use serde::{Deserialize, Serialize};
+
+use super::{line, Block};
+use crate::error::{Error, Result};
+use crate::format::{Template, Value};
+use crate::protocol::{ClickEvent, StatusLine};
+use crate::threshold::{Direction, State, Thresholds};
+use crate::units::Units;
+
+pub const PLACEHOLDERS: &[&str] = &[
+    "used",
+    "free",
+    "total",
+    "percent",
+    "inodes_used",
+    "inodes_free",
+    "inodes_total",
+    "inodes_percent",
+    "mount",
+    "device",
+    "fs",
+];
+
+/// Filesystem types that never hold files of their own, skipped when
+/// listing every mount.
+const PSEUDO_FILESYSTEMS: &[&str] = &[
+    "autofs",
+    "binfmt_misc",
+    "bpf",
+    "cgroup",
+    "cgroup2",
+    "configfs",
+    "debugfs",
+    "devpts",
+    "devtmpfs",
+    "efivarfs",
+    "fusectl",
+    "hugetlbfs",
+    "mqueue",
+    "nsfs",
+    "overlay",
+    "proc",
+    "pstore",
+    "ramfs",
+    "securityfs",
+    "squashfs",
+    "sysfs",
+    "tmpfs",
+    "tracefs",
+];
+
+#[derive(Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Options {
+    /// Mount point of the filesystem to display, `/` when nothing else
+    /// selects one.
+    mount: Option<String>,
+    /// Device of the filesystem to display.
+    device: Option<String>,
+    /// Label of the filesystem to display.
+    label: Option<String>,
+    /// UUID of the filesystem to display.
+    uuid: Option<String>,
+    /// Display every mounted filesystem, one block each.
+    all: bool,
+    /// Filesystem types left out when displaying every filesystem.
+    exclude_types: Vec<String>,
+    format: Template,
+    short_format: Option<Template>,
+    thresholds: Thresholds,
+    units: Units,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            mount: None,
+            device: None,
+            label: None,
+            uuid: None,
+            all: false,
+            exclude_types: PSEUDO_FILESYSTEMS.iter().map(|fs| fs.to_string()).collect(),
+            format: Template::parse(" : {used:.1} / {total:.1}").unwrap(),
+            short_format: None,
+            thresholds: Thresholds::new(None, 80.0, 95.0),
+            units: Units::default(),
+        }
+    }
+}
+
+/// An entry of `/proc/self/mounts`.
+struct Mount {
+    device: String,
+    mount_point: String,
+    fs_type: String,
+}
+
+/// Usage of a mounted filesystem, from `statvfs`.
+struct Usage {
+    mount: Mount,
+    total: u64,
+    free: u64,
+    used: u64,
+    inodes_total: u64,
+    inodes_free: u64,
+    state: State,
+}
+
+impl Usage {
+    /// Share of the space usable by users that is taken, like `df` does.
+    fn percent(&self) -> f64 {
+        percent(self.used, self.used + self.free)
+    }
+
+    fn inodes_percent(&self) -> f64 {
+        percent(self.inodes_total - self.inodes_free, self.inodes_total)
+    }
+}
+
+pub struct Disk {
+    /// Displayed filesystems, in the order of the mount table.
+    usages: Vec<Usage>,
+    options: Options,
+}
+
+impl Disk {
+    pub fn new(options: Options) -> Result<Self, toml::de::Error> {
+        let selectors = [
+            &options.mount,
+            &options.device,
+            &options.label,
+            &options.uuid,
+        ];
+        let selected = selectors.iter().filter(|s| s.is_some()).count();
+        if selected > 1 || (selected == 1 && options.all) {
+            return Err(toml::de::Error::custom(
+                "only one of `mount`, `device`, `label`, `uuid` and `all` can be set",
+            ));
+        }
+        Ok(Disk {
+            usages: vec![],
+            options,
+        })
+    }
+
+    /// The device selected by `device`, `label` or `uuid`, resolved through
+    /// its symlinks.
+    fn selected_device(&self) -> Option<PathBuf> {
+        let path = match (
+            &self.options.device,
+            &self.options.label,
+            &self.options.uuid,
+        ) {
+            (Some(device), _, _) => PathBuf::from(device),
+            (_, Some(label), _) => Path::new("/dev/disk/by-label").join(label),
+            (_, _, Some(uuid)) => Path::new("/dev/disk/by-uuid").join(uuid),
+            _ => return None,
+        };
+        Some(fs::canonicalize(&path).unwrap_or(path))
+    }
+
+    fn is_selected(&self, mount: &Mount, device: Option<&Path>) -> bool {
+        if self.options.all {
+            return !self.options.exclude_types.contains(&mount.fs_type);
+        }
+        match device {
+            Some(device) => fs::canonicalize(&mount.device)
+                .map(|path| path == device)
+                .unwrap_or(false),
+            None => mount.mount_point == self.options.mount.as_deref().unwrap_or("/"),
+        }
+    }
+
+    /// The mounts to display out of the mount table, each device once: bind
+    /// mounts show the same filesystem more than once.
+    fn select(&self, mounts: Vec<Mount>, device: Option<&Path>) -> Vec<Mount> {
+        let mut seen_devices = vec![];
+        let mut selected = vec![];
+        for mount in mounts {
+            if !self.is_selected(&mount, device) || seen_devices.contains(&mount.device) {
+                continue;
+            }
+            seen_devices.push(mount.device.clone());
+            selected.push(mount);
+        }
+        selected
+    }
+}
+
+impl Block for Disk {
+    fn name(&self) -> &'static str {
+        "disk"
+    }
+
+    fn color(&self) -> &'static str {
+        "blue"
+    }
+
+    fn update(&mut self) -> Result<()> {
+        let device = self.selected_device();
+        let previous: HashMap<String, State> = self
+            .usages
+            .drain(..)
+            .map(|usage| (usage.mount.mount_point, usage.state))
+            .collect();
+
+        let mounts = read_mounts(Path::new(MOUNTS))?;
+        for mount in self.select(mounts, device.as_deref()) {
+            let stat = match statvfs(&mount.mount_point) {
+                Ok(stat) => stat,
+                Err(_) if self.options.all => continue,
+                Err(err) => return Err(Error::Read(PathBuf::from(&mount.mount_point), err)),
+            };
+            let block_size = stat.f_frsize as u64;
+            let mut usage = Usage {
+                total: stat.f_blocks as u64 * block_size,
+                free: stat.f_bavail as u64 * block_size,
+                used: (stat.f_blocks as u64).saturating_sub(stat.f_bfree as u64) * block_size,
+                inodes_total: stat.f_files as u64,
+                inodes_free: stat.f_ffree as u64,
+                state: State::Idle,
+                mount,
+            };
+            if self.options.all && usage.total == 0 {
+                continue;
+            }
+            let previous = previous.get(&usage.mount.mount_point).copied();
+            usage.state = self.options.thresholds.state(
+                usage.percent(),
+                previous.unwrap_or(State::Idle),
+                Direction::HigherIsWorse,
+            );
+            self.usages.push(usage);
+        }
+        // Most likely a typo in the configuration, better shown than an empty
+        // block.
+        if !self.options.all && self.usages.is_empty() {
+            return Err(Error::Data(match &device {
+                Some(device) => format!("nothing mounted from {}", device.display()),
+                None => format!(
+                    "nothing mounted on {}",
+                    self.options.mount.as_deref().unwrap_or("/")
+                ),
+            }));
+        }
+        Ok(())
+    }
+
+    fn render(&self) -> Vec<StatusLine> {
+        self.usages
+            .iter()
+            .map(|usage| {
+                let values = [
+                    ("used", Value::Bytes(usage.used as f64, self.options.units)),
+                    ("free", Value::Bytes(usage.free as f64, self.options.units)),
+                    (
+                        "total",
+                        Value::Bytes(usage.total as f64, self.options.units),
+                    ),
+                    ("percent", Value::Number(usage.percent())),
+                    (
+                        "inodes_used",
+                        Value::Number((usage.inodes_total - usage.inodes_free) as f64),
+                    ),
+                    ("inodes_free", Value::Number(usage.inodes_free as f64)),
+                    ("inodes_total", Value::Number(usage.inodes_total as f64)),
+                    ("inodes_percent", Value::Number(usage.inodes_percent())),
+                    ("mount", Value::Text(usage.mount.mount_point.clone())),
+                    ("device", Value::Text(usage.mount.device.clone())),
+                    ("fs", Value::Text(usage.mount.fs_type.clone())),
+                ];
+
+                StatusLine {
+                    instance: Some(usage.mount.mount_point.clone()),
+                    state: usage.state,
+                    ..line(
+                        &self.options.format,
+                        self.options.short_format.as_ref(),
+                        &values,
+                    )
+                }
+            })
+            .collect()
+    }
+
+    fn click(&mut self, event: &ClickEvent) {
+        // Left click only, scrolling over the bar must not open windows.
+        if event.button != 1 {
+            return;
+        }
+        if let Some(mount_point) = &event.instance {
+            match Command::new("xdg-open").arg(mount_point).spawn() {
+                // Waited for so it does not linger as a zombie.
+                Ok(mut child) => {
+                    thread::spawn(move || child.wait());
+                }
+                Err(err) => log::warn!("cannot run xdg-open: {}", err),
+            }
+        }
+    }
+}
+
+fn percent(part: u64, total: u64) -> f64 {
+    match total {
+        0 => 0.0,
+        _ => part as f64 / total as f64 * 100.0,
+    }
+}
+
+const MOUNTS: &str = "/proc/self/mounts";
+
+/// Every mounted filesystem, in the order of the mount table at `path`.
+/// Paths that are not valid UTF-8 are read lossily.
+fn read_mounts(path: &Path) -> Result<Vec<Mount>> {
+    let content = fs::read(path).map_err(|err| Error::Read(path.into(), err))?;
+    let content = String::from_utf8_lossy(&content);
+    let mounts = content
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let device = unescape(fields.next()?);
+            let mount_point = unescape(fields.next()?);
+            let fs_type = fields.next()?.to_string();
+            Some(Mount {
+                device,
+                mount_point,
+                fs_type,
+            })
+        })
+        .collect();
+    Ok(mounts)
+}
+
+/// The mount table escapes spaces and a few other characters as `\ooo`.
+fn unescape(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut unescaped = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' {
+            let octal = bytes
+                .get(i + 1..i + 4)
+                .filter(|digits| digits.iter().all(|digit| (b'0'..=b'7').contains(digit)))
+                .and_then(|digits| std::str::from_utf8(digits).ok())
+                .and_then(|digits| u8::from_str_radix(digits, 8).ok());
+            if let Some(byte) = octal {
+                unescaped.push(byte);
+                i += 4;
+                continue;
+            }
+        }
+        unescaped.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&unescaped).into_owned()
+}
+
+fn statvfs(path: &str) -> io::Result<libc::statvfs> {
+    let path = CString::new(Path::new(path).as_os_str().as_bytes())?;
+    let mut stat = MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: `path` is a valid C string and `stat` is only read once
+    // `statvfs` reported filling it.
+    unsafe {
+        if libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(stat.assume_init())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::fake_sysfs::FakeSysfs;
+    use super::*;
+
+    fn disk(options: &str) -> Disk {
+        Disk::new(toml::from_str(options).unwrap()).unwrap()
+    }
+
+    fn mount(device: &str, mount_point: &str) -> Mount {
+        Mount {
+            device: device.to_string(),
+            mount_point: mount_point.to_string(),
+            fs_type: "ext4".to_string(),
+        }
+    }
+
+    fn mount_points(mounts: &[Mount]) -> Vec<&str> {
+        mounts
+            .iter()
+            .map(|mount| mount.mount_point.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn unescapes_octal_sequences() {
+        assert_eq!(unescape("/mnt/my\\040disk"), "/mnt/my disk");
+        assert_eq!(unescape("a\\011b\\012c\\134d"), "a\tb\nc\\d");
+        assert_eq!(unescape("\\040\\040"), "  ");
+        assert_eq!(unescape("/plain"), "/plain");
+    }
+
+    #[test]
+    fn keeps_invalid_escapes() {
+        // Short, not octal, or out of the range of a byte.
+        assert_eq!(unescape("/mnt\\04"), "/mnt\\04");
+        assert_eq!(unescape("/mnt\\"), "/mnt\\");
+        assert_eq!(unescape("/mnt\\089"), "/mnt\\089");
+        assert_eq!(unescape("/mnt\\+12"), "/mnt\\+12");
+        assert_eq!(unescape("/mnt\\777"), "/mnt\\777");
+        assert_eq!(unescape("\\\\040"), "\\ ");
+    }
+
+    #[test]
+    fn unescapes_bytes_lossily() {
+        assert_eq!(unescape("caf\\303\\251"), "caf\u{e9}");
+        assert_eq!(unescape("/mnt/\\377"), "/mnt/\u{fffd}");
+    }
+
+    #[test]
+    fn reads_the_mount_table() {
+        let proc = FakeSysfs::new("disk-mounts");
+        let mut table = b"/dev/sda1 / ext4 rw,relatime 0 0\n".to_vec();
+        table.extend_from_slice(b"/dev/sdb1 /mnt/my\\040disk vfat rw 0 0\n");
+        table.extend_from_slice(b"/dev/sdc1 /mnt/\xff\\303\\251 ext4 rw 0 0\n");
+        table.extend_from_slice(b"truncated\n\n");
+        fs::write(proc.path().join("mounts"), table).unwrap();
+
+        let mounts = read_mounts(&proc.path().join("mounts")).unwrap();
+        assert_eq!(
+            mount_points(&mounts),
+            ["/", "/mnt/my disk", "/mnt/\u{fffd}\u{e9}"]
+        );
+        assert_eq!(mounts[1].device, "/dev/sdb1");
+        assert_eq!(mounts[1].fs_type, "vfat");
+    }
+
+    #[test]
+    fn fails_without_a_mount_table() {
+        let proc = FakeSysfs::new("disk-no-mounts");
+        assert!(matches!(
+            read_mounts(&proc.path().join("mounts")),
+            Err(Error::Read(..))
+        ));
+    }
+
+    #[test]
+    fn shows_a_device_mounted_twice_once() {
+        let dev = FakeSysfs::new("disk-devices");
+        dev.write("sda1", "").write("sdb1", "");
+        let sda1 = dev.path().join("sda1").display().to_string();
+        let sdb1 = dev.path().join("sdb1").display().to_string();
+        let mounts = || {
+            vec![
+                mount(&sda1, "/"),
+                mount(&sdb1, "/home"),
+                mount(&sdb1, "/srv/home"),
+            ]
+        };
+
+        let single = disk(&format!("device = {:?}", sdb1));
+        let device = single.selected_device();
+        assert_eq!(
+            mount_points(&single.select(mounts(), device.as_deref())),
+            ["/home"]
+        );
+
+        let all = disk("all = true");
+        assert_eq!(mount_points(&all.select(mounts(), None)), ["/", "/home"]);
+    }
+
+    #[test]
+    fn selects_a_mount_point() {
+        let home = disk("mount = \"/home\"");
+        let mounts = vec![mount("/dev/sda1", "/"), mount("/dev/sdb1", "/home")];
+        assert_eq!(mount_points(&home.select(mounts, None)), ["/home"]);
+    }
+}
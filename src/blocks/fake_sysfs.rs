@@ -0,0 +1,40 @@
+//! Directory trees standing in for sysfs in the tests of the blocks.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use crate::config::APP_NAME;
+
+/// A temporary directory removed once dropped.
+pub struct FakeSysfs {
+    root: PathBuf,
+}
+
+impl FakeSysfs {
+    /// `name` tells apart the trees of tests running at the same time.
+    pub fn new(name: &str) -> Self {
+        let root = std::env::temp_dir().join(format!("{}-{}-{}", APP_NAME, process::id(), name));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        FakeSysfs { root }
+    }
+
+    /// Writes `content` to the file at `path`, relative to the root.
+    pub fn write(&self, path: &str, content: &str) -> &Self {
+        let path = self.root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, format!("{}\n", content)).unwrap();
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.root
+    }
+}
+
+impl Drop for FakeSysfs {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
@@ -0,0 +1,93 @@
+use serde::{Deserialize, Serialize};
+use sysinfo::SystemExt;
+
+use super::{line, Block};
+use crate::error::Result;
+use crate::format::{self, Template, Value};
+use crate::protocol::StatusLine;
+use crate::threshold::{Direction, State, Thresholds};
+
+pub const PLACEHOLDERS: &[&str] = &["load1", "load5", "load15", "uptime"];
+
+#[derive(Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Options {
+    format: Template,
+    short_format: Option<Template>,
+    /// Applied to the 1 minute load in percent of the number of cores, so
+    /// 100 means every core is busy.
+    thresholds: Thresholds,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            format: Template::parse(" : {load1:.2} {load5:.2} {load15:.2} |  : {uptime}")
+                .unwrap(),
+            short_format: Some(Template::parse(" : {load1:.2}").unwrap()),
+            thresholds: Thresholds::new(None, 100.0, 200.0),
+        }
+    }
+}
+
+pub struct Load {
+    sys: sysinfo::System,
+    load: sysinfo::LoadAvg,
+    state: State,
+    options: Options,
+}
+
+impl Load {
+    pub fn new(options: Options) -> Result<Self, toml::de::Error> {
+        Ok(Load {
+            sys: sysinfo::System::new(),
+            load: sysinfo::LoadAvg::default(),
+            state: State::Idle,
+            options,
+        })
+    }
+}
+
+impl Block for Load {
+    fn name(&self) -> &'static str {
+        "load"
+    }
+
+    fn color(&self) -> &'static str {
+        "cyan"
+    }
+
+    fn update(&mut self) -> Result<()> {
+        // Also refreshes the uptime and the list of cores.
+        self.sys.refresh_cpu();
+        self.load = self.sys.get_load_average();
+        let cores = self.sys.get_processors().len().max(1);
+        self.state = self.options.thresholds.state(
+            self.load.one / cores as f64 * 100.0,
+            self.state,
+            Direction::HigherIsWorse,
+        );
+        Ok(())
+    }
+
+    fn render(&self) -> Vec<StatusLine> {
+        let values = [
+            ("load1", Value::Number(self.load.one)),
+            ("load5", Value::Number(self.load.five)),
+            ("load15", Value::Number(self.load.fifteen)),
+            (
+                "uptime",
+                Value::Text(format::duration(self.sys.get_uptime())),
+            ),
+        ];
+
+        vec![StatusLine {
+            state: self.state,
+            ..line(
+                &self.options.format,
+                self.options.short_format.as_ref(),
+                &values,
+            )
+        }]
+    }
+}
@@ -0,0 +1,97 @@
+use serde::{Deserialize, Serialize};
+use sysinfo::SystemExt;
+
+use super::{line, Block};
+use crate::error::Result;
+use crate::format::{Template, Value};
+use crate::protocol::StatusLine;
+use crate::threshold::{Direction, State, Thresholds};
+use crate::units::Units;
+
+pub const PLACEHOLDERS: &[&str] = &["used", "free", "total", "percent"];
+
+#[derive(Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Options {
+    format: Template,
+    short_format: Option<Template>,
+    thresholds: Thresholds,
+    units: Units,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            format: Template::parse(" : {used:.1} / {total:.1}").unwrap(),
+            short_format: None,
+            thresholds: Thresholds::new(None, 80.0, 95.0),
+            units: Units::default(),
+        }
+    }
+}
+
+pub struct Memory {
+    sys: sysinfo::System,
+    state: State,
+    options: Options,
+}
+
+impl Memory {
+    pub fn new(options: Options) -> Result<Self, toml::de::Error> {
+        Ok(Memory {
+            sys: sysinfo::System::new(),
+            state: State::Idle,
+            options,
+        })
+    }
+}
+
+impl Memory {
+    fn percent(&self) -> f64 {
+        let total = self.sys.get_total_memory();
+        match total {
+            0 => 0.0,
+            _ => self.sys.get_used_memory() as f64 / total as f64 * 100.0,
+        }
+    }
+}
+
+impl Block for Memory {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    fn color(&self) -> &'static str {
+        "yellow"
+    }
+
+    fn update(&mut self) -> Result<()> {
+        self.sys.refresh_memory();
+        self.state =
+            self.options
+                .thresholds
+                .state(self.percent(), self.state, Direction::HigherIsWorse);
+        Ok(())
+    }
+
+    fn render(&self) -> Vec<StatusLine> {
+        // sysinfo converts /proc/meminfo to kB, i.e. 1000 bytes.
+        let used = self.sys.get_used_memory() as f64 * 1000.0;
+        let total = self.sys.get_total_memory() as f64 * 1000.0;
+        let values = [
+            ("used", Value::Bytes(used, self.options.units)),
+            ("free", Value::Bytes(total - used, self.options.units)),
+            ("total", Value::Bytes(total, self.options.units)),
+            ("percent", Value::Number(self.percent())),
+        ];
+
+        vec![StatusLine {
+            state: self.state,
+            ..line(
+                &self.options.format,
+                self.options.short_format.as_ref(),
+                &values,
+            )
+        }]
+    }
+}
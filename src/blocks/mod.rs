@@ -0,0 +1,771 @@
+mod battery;
+mod cpu;
+mod disk;
+#[cfg(test)]
+pub(crate) mod fake_sysfs;
+mod load;
+mod memory;
+mod network;
+mod os;
+mod temperature;
+mod time;
+
+use std::cmp;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::de::{DeserializeOwned, Error as _};
+use serde::Serialize;
+
+use crate::config::{self, BlockConfig, Config};
+use crate::control::Command;
+use crate::error::{Error, Result};
+use crate::event::{Event, Notifier};
+use crate::format::{Template, Value};
+use crate::logging;
+use crate::protocol::{ClickEvent, StatusLine};
+use crate::scheduler::{Interval, Scheduler};
+use crate::theme::Theme;
+use crate::threshold::State;
+
+/// A piece of the bar, rendering to one or more i3bar blocks.
+pub trait Block {
+    /// Name of the block type, also used as the `name` of rendered blocks so
+    /// click events can be routed back here.
+    fn name(&self) -> &'static str;
+
+    /// Called once before the first update. Blocks fed by events rather than
+    /// polled can spawn their watching threads here and push updates through
+    /// `notifier`.
+    fn start(&mut self, _notifier: Notifier) {}
+
+    /// Palette color of the text when the block is in no particular state.
+    fn color(&self) -> &'static str;
+
+    /// Refreshes the data the block displays. On failure the bar shows the
+    /// error in place of the block and retries later.
+    fn update(&mut self) -> Result<()>;
+
+    /// Builds the status lines out of the last update. A block may render
+    /// nothing, e.g. the battery on a desktop. Colors left unset are picked
+    /// from the theme according to the state of each line, and the bar names
+    /// every line after the block.
+    fn render(&self) -> Vec<StatusLine>;
+
+    /// Reacts to a click on one of the status lines of this block.
+    fn click(&mut self, _event: &ClickEvent) {}
+
+    /// How often to update the block, `None` to use the bar's default
+    /// interval.
+    fn interval(&self) -> Option<Interval> {
+        None
+    }
+}
+
+/// Block types known to the registry, in their default order.
+pub const BLOCK_NAMES: &[&str] = &[
+    "os",
+    "cpu",
+    "load",
+    "temperature",
+    "memory",
+    "disk",
+    "network",
+    "battery",
+    "time",
+];
+
+/// Placeholders the formats of the block type `block` can use.
+pub fn placeholders(block: &str) -> Option<&'static [&'static str]> {
+    Some(match block {
+        "os" => os::PLACEHOLDERS,
+        "cpu" => cpu::PLACEHOLDERS,
+        "load" => load::PLACEHOLDERS,
+        "temperature" => temperature::PLACEHOLDERS,
+        "memory" => memory::PLACEHOLDERS,
+        "disk" => disk::PLACEHOLDERS,
+        "network" => network::PLACEHOLDERS,
+        "battery" => battery::PLACEHOLDERS,
+        "time" => time::PLACEHOLDERS,
+        _ => return None,
+    })
+}
+
+/// Fails when a format given to a block uses a placeholder the block does
+/// not provide. The default formats are known to be valid.
+fn check_formats(
+    options: &toml::value::Table,
+    placeholders: &[&str],
+) -> Result<(), toml::de::Error> {
+    for key in ["format", "short_format"] {
+        if let Some(format) = options.get(key) {
+            let format: Template = format.clone().try_into()?;
+            format
+                .check(placeholders)
+                .map_err(|err| toml::de::Error::custom(format!("in `{}`: {}", key, err)))?;
+        }
+    }
+    Ok(())
+}
+
+/// A status line showing `values` through the formats of a block.
+fn line(
+    format: &Template,
+    short_format: Option<&Template>,
+    values: &[(&str, Value)],
+) -> StatusLine {
+    StatusLine {
+        full_text: format.render(values),
+        short_text: short_format.map(|format| format.render(values)),
+        ..Default::default()
+    }
+}
+
+/// Glob-like matching where `*` matches any run of characters and `?` a
+/// single one.
+fn matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Position of the last `*` in the pattern and of the name when reached.
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Reads a small text file such as a sysfs attribute, trimming its trailing
+/// newline.
+fn read_file(path: &Path) -> Option<String> {
+    fs::read_to_string(path)
+        .ok()
+        .map(|content| content.trim().to_string())
+}
+
+/// Splits the instance of a clicked line into the index of its entry and the
+/// instance the block set itself, if any.
+fn split_instance(instance: &str) -> Option<(usize, Option<&str>)> {
+    let (index, instance) = match instance.split_once(':') {
+        Some((index, instance)) => (index, Some(instance)),
+        None => (instance, None),
+    };
+    Some((index.parse().ok()?, instance))
+}
+
+/// The options of the block described by `config`, with the defaults filled
+/// in.
+pub fn resolve_options(config: &BlockConfig) -> Result<toml::value::Table, toml::de::Error> {
+    fn resolve<T: DeserializeOwned + Serialize>(
+        options: toml::Value,
+    ) -> Result<toml::value::Table, toml::de::Error> {
+        let options: T = options.try_into()?;
+        match toml::Value::try_from(options) {
+            Ok(toml::Value::Table(table)) => Ok(table),
+            Ok(_) => Ok(toml::value::Table::new()),
+            Err(err) => Err(toml::de::Error::custom(err)),
+        }
+    }
+
+    let options = toml::Value::Table(config.options.clone());
+    match config.block.as_str() {
+        "os" => resolve::<os::Options>(options),
+        "cpu" => resolve::<cpu::Options>(options),
+        "load" => resolve::<load::Options>(options),
+        "temperature" => resolve::<temperature::Options>(options),
+        "memory" => resolve::<memory::Options>(options),
+        "disk" => resolve::<disk::Options>(options),
+        "network" => resolve::<network::Options>(options),
+        "battery" => resolve::<battery::Options>(options),
+        "time" => resolve::<time::Options>(options),
+        name => Err(toml::de::Error::custom(format!(
+            "unknown block type `{}`",
+            name
+        ))),
+    }
+}
+
+/// Instantiates the block described by `config`.
+pub fn create(config: &BlockConfig) -> Result<Box<dyn Block>, toml::de::Error> {
+    let options = toml::Value::Table(config.options.clone());
+    let block: Box<dyn Block> = match config.block.as_str() {
+        "os" => Box::new(os::Os::new(options.try_into()?)?),
+        "cpu" => Box::new(cpu::Cpu::new(options.try_into()?)?),
+        "load" => Box::new(load::Load::new(options.try_into()?)?),
+        "temperature" => Box::new(temperature::Temperature::new(options.try_into()?)?),
+        "memory" => Box::new(memory::Memory::new(options.try_into()?)?),
+        "disk" => Box::new(disk::Disk::new(options.try_into()?)?),
+        "network" => Box::new(network::Network::new(options.try_into()?)?),
+        "battery" => Box::new(battery::Battery::new(options.try_into()?)?),
+        "time" => Box::new(time::Time::new(options.try_into()?)?),
+        name => {
+            return Err(toml::de::Error::custom(format!(
+                "unknown block type `{}`, expected one of {}",
+                name,
+                BLOCK_NAMES.join(", ")
+            )))
+        }
+    };
+    check_formats(
+        &config.options,
+        placeholders(&config.block).unwrap_or_default(),
+    )?;
+    Ok(block)
+}
+
+/// A block along with the options common to every block type and its last
+/// rendered output.
+struct Entry {
+    block: Box<dyn Block>,
+    interval: Interval,
+    color: Option<String>,
+    theme: Theme,
+    lines: Vec<StatusLine>,
+    /// Updates failed in a row.
+    failures: u32,
+    /// Set through the control socket: hides the block, or replaces its text
+    /// or state.
+    hidden: bool,
+    text: Option<String>,
+    state: Option<State>,
+}
+
+impl Entry {
+    /// Updates the block and caches its output, returning whether it changed.
+    /// A block failing, or even panicking, is shown as an error instead.
+    /// `index` is the position of the entry in the bar, which prefixes the
+    /// instance of its lines so clicks reach this entry alone.
+    fn refresh(&mut self, index: usize) -> bool {
+        let block = &mut self.block;
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            block.update()?;
+            Ok(block.render())
+        }))
+        .unwrap_or_else(|_| Err(Error::Data("the block panicked".to_string())));
+        let mut lines = match result {
+            Ok(lines) => {
+                if self.failures > 0 {
+                    log::info!(target: &self.log_target(), "recovered");
+                }
+                self.failures = 0;
+                lines
+            }
+            Err(err) => {
+                self.failures += 1;
+                log::warn!(
+                    target: &self.log_target(),
+                    "{}, retrying in {}s",
+                    err,
+                    self.retry_delay().as_secs()
+                );
+                vec![self.error_line(&err)]
+            }
+        };
+        for line in &mut lines {
+            if let Some(text) = &self.text {
+                line.full_text = text.clone();
+                line.short_text = None;
+            }
+            if let Some(state) = self.state {
+                line.state = state;
+            }
+            self.theme
+                .apply(line, self.color.as_deref(), self.block.color());
+            line.name = Some(self.block.name().to_string());
+            line.instance = Some(match line.instance.take() {
+                Some(instance) => format!("{}:{}", index, instance),
+                None => index.to_string(),
+            });
+        }
+        let changed = lines != self.lines;
+        self.lines = lines;
+        changed
+    }
+
+    fn log_target(&self) -> String {
+        logging::block_target(self.block.name())
+    }
+
+    fn error_line(&self, err: &Error) -> StatusLine {
+        let name = self.block.name();
+        StatusLine {
+            full_text: format!("{}: {}", name, err),
+            short_text: Some(format!("{}: error", name)),
+            state: State::Critical,
+            ..Default::default()
+        }
+    }
+
+    /// How long to wait before updating a failing block again, doubling
+    /// with every failure.
+    fn retry_delay(&self) -> Duration {
+        let doublings = cmp::min(self.failures.saturating_sub(1), 16);
+        cmp::min(RETRY_DELAY * 2u32.pow(doublings), MAX_RETRY_DELAY)
+    }
+}
+
+/// Delay before the first retry of a failing block.
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(300);
+
+/// The ordered list of blocks making up the bar.
+pub struct Bar {
+    entries: Vec<Entry>,
+    scheduler: Scheduler,
+    /// Whether the output changed without any block being updated, e.g. when
+    /// a block was hidden.
+    dirty: bool,
+    /// Shared with the notifiers of the blocks, cleared when the bar is
+    /// dropped so their threads exit.
+    active: Arc<AtomicBool>,
+    /// Shared with the notifiers of the blocks, set while the bar is hidden.
+    paused: Arc<AtomicBool>,
+}
+
+impl Bar {
+    /// Builds the bar from the configuration, failing on the first block that
+    /// cannot be created.
+    pub fn new(config: &Config) -> Result<Bar, config::Error> {
+        let theme =
+            Theme::load(&config.theme, config.dir.as_deref()).map_err(config::Error::Theme)?;
+        let mut entries = vec![];
+        for (index, block_config) in config.blocks.iter().enumerate() {
+            let block_error = |source| config::Error::Block {
+                index,
+                block: block_config.block.clone(),
+                source,
+            };
+            let block = create(block_config).map_err(block_error)?;
+            let block_theme = match &block_config.theme {
+                Some(overrides) => theme
+                    .with_overrides(overrides, config.dir.as_deref())
+                    .map_err(|err| {
+                        block_error(toml::de::Error::custom(format!("in `theme`: {}", err)))
+                    })?,
+                None => theme.clone(),
+            };
+            if let Some(color) = &block_config.color {
+                block_theme.check_color(color).map_err(|err| {
+                    block_error(toml::de::Error::custom(format!("in `color`: {}", err)))
+                })?;
+            }
+            let interval = block_config
+                .interval
+                .or_else(|| block.interval())
+                .unwrap_or(config.interval);
+            entries.push(Entry {
+                block,
+                interval,
+                color: block_config.color.clone(),
+                theme: block_theme,
+                lines: vec![],
+                failures: 0,
+                hidden: false,
+                text: None,
+                state: None,
+            });
+        }
+        Ok(Bar {
+            scheduler: Scheduler::new(entries.len(), Instant::now()),
+            entries,
+            dirty: false,
+            active: Arc::new(AtomicBool::new(true)),
+            paused: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    /// Starts every block, `tx` being where their update requests go.
+    pub fn start(&mut self, tx: &Sender<Event>) {
+        for (index, entry) in self.entries.iter_mut().enumerate() {
+            let notifier =
+                Notifier::new(index, tx.clone(), self.active.clone(), self.paused.clone());
+            entry.block.start(notifier);
+        }
+    }
+
+    /// Updates every block due at `now`, returning whether the output of the
+    /// bar changed.
+    pub fn update(&mut self, now: Instant) -> bool {
+        let mut changed = std::mem::take(&mut self.dirty);
+        for (index, entry) in self.entries.iter_mut().enumerate() {
+            if self.scheduler.is_due(index, now) {
+                changed |= entry.refresh(index);
+                match entry.failures {
+                    0 => self.scheduler.reschedule(index, now, entry.interval),
+                    _ => self.scheduler.delay(index, now, entry.retry_delay()),
+                }
+            }
+        }
+        changed
+    }
+
+    pub fn render(&self) -> Vec<StatusLine> {
+        self.entries
+            .iter()
+            .filter(|entry| !entry.hidden)
+            .flat_map(|entry| entry.lines.iter().cloned())
+            .collect()
+    }
+
+    /// Forwards a click to the block it was made on, which gets updated on
+    /// the next call to `update`. The block gets back the instance it set
+    /// itself, without the index of its entry.
+    pub fn click(&mut self, event: &ClickEvent) {
+        let name = match event.name.as_deref() {
+            Some(name) => name,
+            None => return,
+        };
+        let (index, instance) = match event.instance.as_deref().map(split_instance) {
+            Some(Some((index, instance))) => (index, instance),
+            _ => return,
+        };
+        let entry = match self.entries.get_mut(index) {
+            Some(entry) if entry.block.name() == name => entry,
+            _ => return,
+        };
+        let event = ClickEvent {
+            instance: instance.map(str::to_string),
+            ..event.clone()
+        };
+        log::debug!(
+            target: &entry.log_target(),
+            "button {} clicked on {:?}",
+            event.button,
+            event.instance
+        );
+        entry.block.click(&event);
+        self.scheduler.wake(index, Instant::now());
+    }
+
+    /// Makes the block at `index` due right away, when it pushed an update.
+    pub fn wake(&mut self, index: usize) {
+        if index < self.entries.len() {
+            self.scheduler.wake(index, Instant::now());
+        }
+    }
+
+    /// Applies a command of the control socket, failing when it names no
+    /// block of the bar. Blocks are named after their type, which addresses
+    /// every block of that type, or `type#n` for the n-th of them only.
+    pub fn control(&mut self, command: &Command) -> Result<(), String> {
+        let address = match command {
+            Command::Refresh(None) => {
+                self.wake_all();
+                return Ok(());
+            }
+            Command::Refresh(Some(name))
+            | Command::SetText(name, _)
+            | Command::SetState(name, _)
+            | Command::Toggle(name) => name,
+            Command::Reload => return Err("the bar cannot reload itself".to_string()),
+        };
+        let (name, nth) = match address.split_once('#') {
+            Some((name, nth)) => match nth.parse::<usize>() {
+                Ok(nth) if nth > 0 => (name, Some(nth)),
+                _ => return Err(format!("invalid block number in `{}`", address)),
+            },
+            None => (address.as_str(), None),
+        };
+        let now = Instant::now();
+        let mut found = false;
+        let of_type = self
+            .entries
+            .iter_mut()
+            .enumerate()
+            .filter(|(_, entry)| entry.block.name() == name);
+        for (n, (index, entry)) in of_type.enumerate() {
+            if nth.is_some_and(|nth| nth != n + 1) {
+                continue;
+            }
+            found = true;
+            match command {
+                Command::SetText(_, text) => entry.text = text.clone(),
+                Command::SetState(_, state) => entry.state = *state,
+                Command::Toggle(_) => {
+                    entry.hidden = !entry.hidden;
+                    self.dirty = true;
+                }
+                _ => {}
+            }
+            self.scheduler.wake(index, now);
+        }
+        match found {
+            true => Ok(()),
+            false => Err(format!("no block named `{}`", address)),
+        }
+    }
+
+    /// Tells the threads of the blocks whether i3bar hides the bar.
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+    }
+
+    /// Makes every block due right away.
+    pub fn wake_all(&mut self) {
+        let now = Instant::now();
+        for index in 0..self.entries.len() {
+            self.scheduler.wake(index, now);
+        }
+    }
+
+    /// When the next block is due, `None` if no block will ever be updated
+    /// again.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.scheduler.next_deadline()
+    }
+}
+
+impl Drop for Bar {
+    fn drop(&mut self) {
+        self.active.store(false, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// Renders one line per mount point and records the clicks it gets.
+    struct Probe {
+        mounts: Vec<&'static str>,
+        clicks: Rc<RefCell<Vec<ClickEvent>>>,
+    }
+
+    impl Block for Probe {
+        fn name(&self) -> &'static str {
+            "disk"
+        }
+
+        fn color(&self) -> &'static str {
+            "green"
+        }
+
+        fn update(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        fn render(&self) -> Vec<StatusLine> {
+            self.mounts
+                .iter()
+                .map(|mount| StatusLine {
+                    full_text: mount.to_string(),
+                    instance: Some(mount.to_string()),
+                    ..Default::default()
+                })
+                .collect()
+        }
+
+        fn click(&mut self, event: &ClickEvent) {
+            self.clicks.borrow_mut().push(event.clone());
+        }
+    }
+
+    /// A bar of two probes, both showing `/home`, along with their clicks.
+    fn bar() -> (Bar, Vec<Rc<RefCell<Vec<ClickEvent>>>>) {
+        let theme = Theme::load(crate::theme::DEFAULT_THEME, None).unwrap();
+        let mut clicks = vec![];
+        let mut entries = vec![];
+        for mounts in [vec!["/", "/home"], vec!["/home"]] {
+            let probe_clicks = Rc::new(RefCell::new(vec![]));
+            clicks.push(probe_clicks.clone());
+            entries.push(Entry {
+                block: Box::new(Probe {
+                    mounts,
+                    clicks: probe_clicks,
+                }),
+                interval: Interval::Once,
+                color: None,
+                theme: theme.clone(),
+                lines: vec![],
+                failures: 0,
+                hidden: false,
+                text: None,
+                state: None,
+            });
+        }
+        let mut bar = Bar {
+            scheduler: Scheduler::new(entries.len(), Instant::now()),
+            entries,
+            dirty: false,
+            active: Arc::new(AtomicBool::new(true)),
+            paused: Arc::new(AtomicBool::new(false)),
+        };
+        bar.update(Instant::now());
+        (bar, clicks)
+    }
+
+    fn click(name: &str, instance: &str) -> ClickEvent {
+        ClickEvent {
+            name: Some(name.to_string()),
+            instance: Some(instance.to_string()),
+            button: 1,
+            ..Default::default()
+        }
+    }
+
+    fn texts(bar: &Bar) -> Vec<String> {
+        bar.render()
+            .into_iter()
+            .map(|line| line.full_text)
+            .collect()
+    }
+
+    #[test]
+    fn prefixes_instances_with_the_entry_index() {
+        let (bar, _) = bar();
+        let instances: Vec<_> = bar.render().into_iter().map(|line| line.instance).collect();
+        assert_eq!(
+            instances,
+            [Some("0:/"), Some("0:/home"), Some("1:/home")].map(|i| i.map(str::to_string))
+        );
+    }
+
+    #[test]
+    fn names_lines_after_their_block() {
+        let (bar, _) = bar();
+        let lines = bar.render();
+        assert!(lines
+            .iter()
+            .all(|line| line.name.as_deref() == Some("disk")));
+    }
+
+    #[test]
+    fn checks_the_placeholders_of_formats() {
+        let config = |options: &str| -> BlockConfig {
+            toml::from_str(&format!("block = \"memory\"\n{}", options)).unwrap()
+        };
+        assert!(create(&config("format = \"{used}/{total}\"")).is_ok());
+        let err = create(&config("format = \"{used} {load}\"")).err().unwrap();
+        assert!(err.to_string().starts_with("in `format`: "), "{}", err);
+        let err = create(&config("short_format = \"{cores}\"")).err().unwrap();
+        assert!(
+            err.to_string().starts_with("in `short_format`: "),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn default_formats_use_known_placeholders() {
+        for name in BLOCK_NAMES {
+            let config: BlockConfig = toml::from_str(&format!("block = {:?}", name)).unwrap();
+            let options = resolve_options(&config).unwrap();
+            check_formats(&options, placeholders(name).unwrap()).unwrap();
+        }
+    }
+
+    #[test]
+    fn routes_clicks_to_their_entry_alone() {
+        let (mut bar, clicks) = bar();
+        bar.click(&click("disk", "1:/home"));
+        assert!(clicks[0].borrow().is_empty());
+        let second = clicks[1].borrow();
+        assert_eq!(second.len(), 1);
+        assert_eq!(second[0].instance.as_deref(), Some("/home"));
+    }
+
+    #[test]
+    fn drops_clicks_on_unknown_entries() {
+        let (mut bar, clicks) = bar();
+        bar.click(&click("disk", "2:/home"));
+        bar.click(&click("cpu", "0:/home"));
+        bar.click(&click("disk", "/home"));
+        assert!(clicks.iter().all(|clicks| clicks.borrow().is_empty()));
+    }
+
+    #[test]
+    fn matches_globs() {
+        assert!(matches("eth0", "eth0"));
+        assert!(!matches("eth0", "eth1"));
+        assert!(!matches("eth", "eth0"));
+        assert!(matches("eth?", "eth0"));
+        assert!(!matches("eth?", "eth"));
+        assert!(matches("*", ""));
+        assert!(matches("", ""));
+        assert!(!matches("", "lo"));
+        assert!(matches("veth*", "veth12ab"));
+        assert!(matches("*0", "wlan0"));
+        assert!(matches("**", "lo"));
+        assert!(matches("br-*-*", "br-a-b-c"));
+        assert!(!matches("br-*-*", "br-abc"));
+    }
+
+    #[test]
+    fn backtracks_on_stars() {
+        // The first `n` reached by the star is not the one ending the name.
+        assert!(matches("*n", "wlan0n"));
+        assert!(matches("*an?", "wlanwlan0"));
+        assert!(matches("a*b*c", "aXbYbZc"));
+        assert!(!matches("a*b*c", "aXbYcZ"));
+        assert!(matches("*.mount", "home.mount.mount"));
+    }
+
+    #[test]
+    fn splits_instances() {
+        assert_eq!(split_instance("3"), Some((3, None)));
+        assert_eq!(split_instance("1:/home"), Some((1, Some("/home"))));
+        assert_eq!(split_instance("1:a:b"), Some((1, Some("a:b"))));
+        assert_eq!(split_instance("/home"), None);
+    }
+
+    #[test]
+    fn controls_the_nth_block_of_a_type_alone() {
+        let (mut bar, _) = bar();
+        let text = Command::SetText("disk#2".to_string(), Some("full".to_string()));
+        bar.control(&text).unwrap();
+        bar.update(Instant::now());
+        assert_eq!(texts(&bar), ["/", "/home", "full"]);
+
+        bar.control(&Command::Toggle("disk#2".to_string())).unwrap();
+        assert!(bar.update(Instant::now()));
+        assert_eq!(texts(&bar), ["/", "/home"]);
+    }
+
+    #[test]
+    fn controls_every_block_of_a_type() {
+        let (mut bar, _) = bar();
+        let text = Command::SetText("disk".to_string(), Some("full".to_string()));
+        bar.control(&text).unwrap();
+        bar.update(Instant::now());
+        assert_eq!(texts(&bar), ["full", "full", "full"]);
+    }
+
+    #[test]
+    fn rejects_unknown_blocks() {
+        let (mut bar, _) = bar();
+        for (address, error) in [
+            ("cpu", "no block named `cpu`"),
+            ("disk#3", "no block named `disk#3`"),
+            ("disk#0", "invalid block number in `disk#0`"),
+            ("disk#x", "invalid block number in `disk#x`"),
+        ] {
+            let command = Command::Toggle(address.to_string());
+            assert_eq!(bar.control(&command).unwrap_err(), error);
+        }
+        assert_eq!(texts(&bar), ["/", "/home", "/home"]);
+    }
+}
@@ -0,0 +1,328 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+use sysinfo::{NetworkExt, SystemExt};
+
+use super::{line, matches, Block};
+use crate::error::Result;
+use crate::format::{Template, Value};
+use crate::protocol::StatusLine;
+use crate::units::Units;
+
+pub const PLACEHOLDERS: &[&str] = &["interface", "rx", "tx", "rx_total", "tx_total"];
+
+#[derive(Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Options {
+    /// Only interface to display. When unset, the interface carrying the
+    /// default route is picked, or every interface if `all` is set.
+    interface: Option<String>,
+    all: bool,
+    /// Patterns (`*` and `?` wildcards) of the interfaces to consider, every
+    /// interface when empty.
+    include: Vec<String>,
+    /// Patterns of the interfaces to leave out.
+    exclude: Vec<String>,
+    /// Whether to consider virtual interfaces (bridges, veth, ...).
+    #[serde(rename = "virtual")]
+    include_virtual: bool,
+    format: Template,
+    short_format: Option<Template>,
+    units: Units,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            interface: None,
+            all: false,
+            include: vec![],
+            exclude: vec!["lo".to_string()],
+            include_virtual: false,
+            format: Template::parse(" : {rx:>8.1}/s |  : {tx:>8.1}/s").unwrap(),
+            short_format: None,
+            units: Units::default(),
+        }
+    }
+}
+
+/// Throughput of an interface over the last refresh, in bytes per second.
+struct Rates {
+    rx: f64,
+    tx: f64,
+    rx_total: u64,
+    tx_total: u64,
+}
+
+pub struct Network {
+    sys: sysinfo::System,
+    last_refresh: Option<Instant>,
+    /// Displayed interfaces, by name.
+    rates: BTreeMap<String, Rates>,
+    options: Options,
+}
+
+impl Network {
+    pub fn new(options: Options) -> Result<Self, toml::de::Error> {
+        Ok(Network {
+            sys: sysinfo::System::new(),
+            last_refresh: None,
+            rates: BTreeMap::new(),
+            options,
+        })
+    }
+
+    /// Whether `interface` passes the filters of the block.
+    fn is_selected(&self, interface: &str) -> bool {
+        if let Some(only) = &self.options.interface {
+            return interface == only;
+        }
+        let included = self.options.include.is_empty()
+            || self
+                .options
+                .include
+                .iter()
+                .any(|pattern| matches(pattern, interface));
+        let excluded = self
+            .options
+            .exclude
+            .iter()
+            .any(|pattern| matches(pattern, interface));
+        included && !excluded && (self.options.include_virtual || !is_virtual(interface))
+    }
+}
+
+impl Block for Network {
+    fn name(&self) -> &'static str {
+        "network"
+    }
+
+    fn color(&self) -> &'static str {
+        "magenta"
+    }
+
+    fn update(&mut self) -> Result<()> {
+        // Also refreshes the counters of known interfaces.
+        self.sys.refresh_networks_list();
+        let now = Instant::now();
+        let elapsed = self
+            .last_refresh
+            .map(|last| now.duration_since(last).as_secs_f64());
+        self.last_refresh = Some(now);
+
+        let default_interface = match self.options.interface.is_none() && !self.options.all {
+            true => default_route_interface(Path::new(PROC_NET)),
+            false => None,
+        };
+        log::trace!("default route through {:?}", default_interface);
+
+        let mut rates = BTreeMap::new();
+        for (interface, data) in self.sys.get_networks() {
+            let selected = match &default_interface {
+                // Picked by the route rather than the filters, so a VPN shows
+                // up although its interface is virtual.
+                Some(default_interface) => interface == default_interface,
+                None => self.is_selected(interface),
+            };
+            if !selected {
+                continue;
+            }
+            // The first refresh has nothing to compare with.
+            let rate = |bytes: u64| match elapsed {
+                Some(elapsed) if elapsed > 0.0 => bytes as f64 / elapsed,
+                _ => 0.0,
+            };
+            rates.insert(
+                interface.clone(),
+                Rates {
+                    rx: rate(data.get_received()),
+                    tx: rate(data.get_transmitted()),
+                    rx_total: data.get_total_received(),
+                    tx_total: data.get_total_transmitted(),
+                },
+            );
+        }
+        self.rates = rates;
+        Ok(())
+    }
+
+    fn render(&self) -> Vec<StatusLine> {
+        self.rates
+            .iter()
+            .map(|(interface, rates)| {
+                let values = [
+                    ("interface", Value::Text(interface.clone())),
+                    ("rx", Value::Bytes(rates.rx, self.options.units)),
+                    ("tx", Value::Bytes(rates.tx, self.options.units)),
+                    (
+                        "rx_total",
+                        Value::Bytes(rates.rx_total as f64, self.options.units),
+                    ),
+                    (
+                        "tx_total",
+                        Value::Bytes(rates.tx_total as f64, self.options.units),
+                    ),
+                ];
+
+                StatusLine {
+                    instance: Some(interface.clone()),
+                    ..line(
+                        &self.options.format,
+                        self.options.short_format.as_ref(),
+                        &values,
+                    )
+                }
+            })
+            .collect()
+    }
+}
+
+/// Virtual interfaces (loopback, bridges, veth pairs, ...) live under
+/// `/sys/devices/virtual`.
+fn is_virtual(interface: &str) -> bool {
+    match fs::canonicalize(Path::new("/sys/class/net").join(interface)) {
+        Ok(path) => path.starts_with("/sys/devices/virtual"),
+        Err(_) => false,
+    }
+}
+
+/// Where the kernel lists the routing tables.
+const PROC_NET: &str = "/proc/net";
+
+/// The interface of the IPv4 default route with the lowest metric, falling
+/// back to the IPv6 one. `proc_net` stands for `/proc/net`.
+fn default_route_interface(proc_net: &Path) -> Option<String> {
+    const RTF_UP: u32 = 0x1;
+
+    let mut best: Option<(u32, String)> = None;
+    if let Ok(routes) = fs::read_to_string(proc_net.join("route")) {
+        for line in routes.lines().skip(1) {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 7 || fields[1] != "00000000" {
+                continue;
+            }
+            let flags = u32::from_str_radix(fields[3], 16).unwrap_or(0);
+            let metric = fields[6].parse().unwrap_or(u32::MAX);
+            if flags & RTF_UP != 0 && best.as_ref().is_none_or(|(m, _)| metric < *m) {
+                best = Some((metric, fields[0].to_string()));
+            }
+        }
+    }
+    if best.is_none() {
+        if let Ok(routes) = fs::read_to_string(proc_net.join("ipv6_route")) {
+            for line in routes.lines() {
+                let fields: Vec<&str> = line.split_whitespace().collect();
+                if fields.len() < 10 || fields[0] != "0".repeat(32) || fields[1] != "00" {
+                    continue;
+                }
+                let metric = u32::from_str_radix(fields[5], 16).unwrap_or(u32::MAX);
+                if fields[9] != "lo" && best.as_ref().is_none_or(|(m, _)| metric < *m) {
+                    best = Some((metric, fields[9].to_string()));
+                }
+            }
+        }
+    }
+    best.map(|(_, interface)| interface)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::fake_sysfs::FakeSysfs;
+    use super::*;
+
+    const ROUTE_HEADER: &str =
+        "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT";
+    const IPV6_DEFAULT: &str =
+        "00000000000000000000000000000000 00 00000000000000000000000000000000 00";
+
+    fn route(interface: &str, destination: &str, flags: &str, metric: u32) -> String {
+        format!(
+            "{}\t{}\t0101A8C0\t{}\t0\t0\t{}\t00000000\t0\t0\t0",
+            interface, destination, flags, metric
+        )
+    }
+
+    fn ipv6_route(interface: &str, metric: u32) -> String {
+        format!(
+            "{} fe800000000000000000000000000001 {:08x} 00000001 00000000 00000003 {}",
+            IPV6_DEFAULT, metric, interface
+        )
+    }
+
+    #[test]
+    fn picks_the_default_route_with_the_lowest_metric() {
+        let proc_net = FakeSysfs::new("network-metric");
+        proc_net.write(
+            "route",
+            &[
+                ROUTE_HEADER.to_string(),
+                route("eth0", "00000000", "0003", 100),
+                route("wlan0", "00000000", "0003", 600),
+                route("tun0", "0000A8C0", "0001", 0),
+                route("wwan0", "00000000", "0003", 50),
+            ]
+            .join("\n"),
+        );
+        assert_eq!(
+            default_route_interface(proc_net.path()).as_deref(),
+            Some("wwan0")
+        );
+    }
+
+    #[test]
+    fn skips_routes_that_are_down() {
+        let proc_net = FakeSysfs::new("network-down");
+        proc_net.write(
+            "route",
+            &[
+                ROUTE_HEADER.to_string(),
+                route("eth0", "00000000", "0002", 0),
+                route("wlan0", "00000000", "0003", 600),
+            ]
+            .join("\n"),
+        );
+        assert_eq!(
+            default_route_interface(proc_net.path()).as_deref(),
+            Some("wlan0")
+        );
+    }
+
+    #[test]
+    fn falls_back_to_the_ipv6_default_route() {
+        let proc_net = FakeSysfs::new("network-ipv6");
+        proc_net
+            .write(
+                "route",
+                &[
+                    ROUTE_HEADER.to_string(),
+                    route("eth0", "0000A8C0", "0001", 0),
+                ]
+                .join("\n"),
+            )
+            .write(
+                "ipv6_route",
+                &[
+                    // The kernel lists an unreachable default route on `lo`.
+                    ipv6_route("lo", 0),
+                    ipv6_route("wlan0", 600),
+                    ipv6_route("eth0", 1024),
+                ]
+                .join("\n"),
+            );
+        assert_eq!(
+            default_route_interface(proc_net.path()).as_deref(),
+            Some("wlan0")
+        );
+    }
+
+    #[test]
+    fn finds_no_route_without_routing_tables() {
+        let proc_net = FakeSysfs::new("network-none");
+        assert_eq!(default_route_interface(proc_net.path()), None);
+        proc_net.write("ipv6_route", &ipv6_route("lo", 0));
+        assert_eq!(default_route_interface(proc_net.path()), None);
+    }
+}
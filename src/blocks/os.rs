@@ -0,0 +1,77 @@
+use serde::{Deserialize, Serialize};
+use sysinfo::SystemExt;
+
+use super::{line, Block};
+use crate::error::{Error, Result};
+use crate::format::{Template, Value};
+use crate::protocol::StatusLine;
+use crate::scheduler::Interval;
+
+pub const PLACEHOLDERS: &[&str] = &["version"];
+
+#[derive(Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Options {
+    format: Template,
+    short_format: Option<Template>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            format: Template::parse("{version}").unwrap(),
+            short_format: None,
+        }
+    }
+}
+
+pub struct Os {
+    sys: sysinfo::System,
+    version: String,
+    options: Options,
+}
+
+impl Os {
+    pub fn new(options: Options) -> Result<Self, toml::de::Error> {
+        Ok(Os {
+            sys: sysinfo::System::new(),
+            version: String::new(),
+            options,
+        })
+    }
+}
+
+impl Block for Os {
+    fn name(&self) -> &'static str {
+        "os"
+    }
+
+    fn color(&self) -> &'static str {
+        "red"
+    }
+
+    fn update(&mut self) -> Result<()> {
+        match self.sys.get_long_os_version() {
+            Some(version) => {
+                self.version = version;
+                Ok(())
+            }
+            None => Err(Error::Data("cannot find the OS version".to_string())),
+        }
+    }
+
+    /// The OS version does not change while the bar is running.
+    fn interval(&self) -> Option<Interval> {
+        Some(Interval::Once)
+    }
+
+    fn render(&self) -> Vec<StatusLine> {
+        let values = [("version", Value::Text(self.version.clone()))];
+
+        vec![line(
+            &self.options.format,
+            self.options.short_format.as_ref(),
+            &values,
+        )]
+    }
+}
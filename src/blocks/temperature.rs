@@ -0,0 +1,354 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::{line, matches, read_file, Block};
+use crate::error::Result;
+use crate::format::{Template, Value};
+use crate::protocol::StatusLine;
+use crate::threshold::{Direction, State, Thresholds};
+
+pub const PLACEHOLDERS: &[&str] = &["temperature", "max", "average", "sensor", "unit"];
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Aggregate {
+    Max,
+    Average,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Scale {
+    Celsius,
+    Fahrenheit,
+}
+
+impl Scale {
+    fn convert(self, celsius: f64) -> f64 {
+        match self {
+            Scale::Celsius => celsius,
+            Scale::Fahrenheit => celsius * 9.0 / 5.0 + 32.0,
+        }
+    }
+
+    fn unit(self) -> &'static str {
+        match self {
+            Scale::Celsius => "°C",
+            Scale::Fahrenheit => "°F",
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Options {
+    /// Pattern (`*` and `?` wildcards) of the chips to read, e.g.
+    /// `coretemp` or `k10temp`.
+    chip: Option<String>,
+    /// Pattern of the sensor labels to read, e.g. `Package id *`.
+    label: Option<String>,
+    /// How the matching sensors make up `{temperature}`.
+    aggregate: Aggregate,
+    scale: Scale,
+    /// Where sysfs is mounted.
+    sysfs: PathBuf,
+    format: Template,
+    short_format: Option<Template>,
+    /// Applied to `{temperature}` in degrees Celsius, whatever the scale.
+    thresholds: Thresholds,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            chip: None,
+            label: None,
+            aggregate: Aggregate::Max,
+            scale: Scale::Celsius,
+            sysfs: PathBuf::from("/sys"),
+            format: Template::parse(" : {temperature:.0}{unit}").unwrap(),
+            short_format: None,
+            thresholds: Thresholds::new(None, 70.0, 85.0),
+        }
+    }
+}
+
+/// A temperature sensor, in degrees Celsius.
+struct Sensor {
+    chip: String,
+    label: String,
+    celsius: f64,
+}
+
+/// Sensors of `<sysfs>/class/hwmon`, temperatures being given in
+/// millidegrees by `tempN_input` files, labelled by `tempN_label`.
+fn hwmon_sensors(sysfs: &Path) -> Vec<Sensor> {
+    let mut sensors = vec![];
+    let chips = match fs::read_dir(sysfs.join("class/hwmon")) {
+        Ok(chips) => chips,
+        Err(_) => return sensors,
+    };
+    for chip in chips.flatten() {
+        let path = chip.path();
+        let name = read_file(&path.join("name")).unwrap_or_default();
+        let mut inputs: Vec<String> = match fs::read_dir(&path) {
+            Ok(files) => files
+                .flatten()
+                .filter_map(|file| file.file_name().into_string().ok())
+                .filter(|file| file.starts_with("temp") && file.ends_with("_input"))
+                .collect(),
+            Err(_) => continue,
+        };
+        inputs.sort();
+        for input in inputs {
+            let sensor = input.trim_end_matches("_input");
+            let millidegrees: f64 = match read_file(&path.join(&input)).and_then(|t| t.parse().ok())
+            {
+                Some(millidegrees) => millidegrees,
+                None => continue,
+            };
+            sensors.push(Sensor {
+                chip: name.clone(),
+                label: read_file(&path.join(format!("{}_label", sensor)))
+                    .unwrap_or_else(|| sensor.to_string()),
+                celsius: millidegrees / 1000.0,
+            });
+        }
+    }
+    sensors
+}
+
+/// Sensors of `<sysfs>/class/thermal`, named after the type of their zone.
+fn thermal_sensors(sysfs: &Path) -> Vec<Sensor> {
+    let zones = match fs::read_dir(sysfs.join("class/thermal")) {
+        Ok(zones) => zones,
+        Err(_) => return vec![],
+    };
+    let mut paths: Vec<PathBuf> = zones
+        .flatten()
+        .map(|zone| zone.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("thermal_zone"))
+        })
+        .collect();
+    paths.sort();
+    paths
+        .iter()
+        .filter_map(|path| {
+            let zone_type = read_file(&path.join("type")).unwrap_or_default();
+            let millidegrees: f64 = read_file(&path.join("temp"))?.parse().ok()?;
+            Some(Sensor {
+                chip: zone_type.clone(),
+                label: zone_type,
+                celsius: millidegrees / 1000.0,
+            })
+        })
+        .collect()
+}
+
+pub struct Temperature {
+    /// Hottest matching sensor.
+    hottest: Option<Sensor>,
+    max: f64,
+    average: f64,
+    state: State,
+    options: Options,
+}
+
+impl Temperature {
+    pub fn new(options: Options) -> Result<Self, toml::de::Error> {
+        Ok(Temperature {
+            hottest: None,
+            max: 0.0,
+            average: 0.0,
+            state: State::Idle,
+            options,
+        })
+    }
+
+    fn is_selected(&self, sensor: &Sensor) -> bool {
+        let chip = self.options.chip.as_deref();
+        let label = self.options.label.as_deref();
+        chip.is_none_or(|chip| matches(chip, &sensor.chip))
+            && label.is_none_or(|label| matches(label, &sensor.label))
+    }
+
+    /// The selected sensors. Thermal zones mostly duplicate hwmon sensors,
+    /// they are only read when no hwmon sensor is selected, e.g. on machines
+    /// without hwmon or when `chip` names a thermal zone.
+    fn sensors(&self) -> Vec<Sensor> {
+        let mut sensors = hwmon_sensors(&self.options.sysfs);
+        sensors.retain(|sensor| self.is_selected(sensor));
+        if sensors.is_empty() {
+            sensors = thermal_sensors(&self.options.sysfs);
+            sensors.retain(|sensor| self.is_selected(sensor));
+        }
+        sensors
+    }
+
+    fn temperature(&self) -> f64 {
+        match self.options.aggregate {
+            Aggregate::Max => self.max,
+            Aggregate::Average => self.average,
+        }
+    }
+}
+
+impl Block for Temperature {
+    fn name(&self) -> &'static str {
+        "temperature"
+    }
+
+    fn color(&self) -> &'static str {
+        "blue"
+    }
+
+    fn update(&mut self) -> Result<()> {
+        let sensors = self.sensors();
+        if sensors.is_empty() {
+            log::debug!("no sensor found in {}", self.options.sysfs.display());
+        }
+
+        self.average = match sensors.len() {
+            0 => 0.0,
+            len => sensors.iter().map(|sensor| sensor.celsius).sum::<f64>() / len as f64,
+        };
+        self.hottest = sensors
+            .into_iter()
+            .max_by(|a, b| a.celsius.total_cmp(&b.celsius));
+        self.max = self.hottest.as_ref().map_or(0.0, |sensor| sensor.celsius);
+        self.state =
+            self.options
+                .thresholds
+                .state(self.temperature(), self.state, Direction::HigherIsWorse);
+        Ok(())
+    }
+
+    fn render(&self) -> Vec<StatusLine> {
+        // No sensor, e.g. in a virtual machine.
+        let hottest = match &self.hottest {
+            Some(hottest) => hottest,
+            None => return vec![],
+        };
+        let scale = self.options.scale;
+        let values = [
+            (
+                "temperature",
+                Value::Number(scale.convert(self.temperature())),
+            ),
+            ("max", Value::Number(scale.convert(self.max))),
+            ("average", Value::Number(scale.convert(self.average))),
+            ("sensor", Value::Text(hottest.label.clone())),
+            ("unit", Value::Text(scale.unit().to_string())),
+        ];
+
+        vec![StatusLine {
+            state: self.state,
+            ..line(
+                &self.options.format,
+                self.options.short_format.as_ref(),
+                &values,
+            )
+        }]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::fake_sysfs::FakeSysfs;
+
+    fn sysfs(name: &str) -> FakeSysfs {
+        let sysfs = FakeSysfs::new(name);
+        sysfs
+            .write("class/hwmon/hwmon0/name", "coretemp")
+            .write("class/hwmon/hwmon0/temp1_input", "60000")
+            .write("class/hwmon/hwmon0/temp1_label", "Package id 0")
+            .write("class/hwmon/hwmon0/temp2_input", "50000")
+            .write("class/hwmon/hwmon0/temp2_label", "Core 0")
+            .write("class/hwmon/hwmon1/name", "amdgpu")
+            .write("class/hwmon/hwmon1/temp1_input", "70000")
+            .write("class/hwmon/hwmon1/temp1_label", "edge")
+            .write("class/thermal/thermal_zone0/type", "x86_pkg_temp")
+            .write("class/thermal/thermal_zone0/temp", "40000");
+        sysfs
+    }
+
+    /// Updates a block made of `options` and returns its text.
+    fn render(sysfs: &FakeSysfs, options: &str) -> Option<String> {
+        let options = format!(
+            "sysfs = {:?}\nformat = \"{{temperature:.1}}{{unit}} {{sensor}}\"\n{}",
+            sysfs.path(),
+            options
+        );
+        let mut block = Temperature::new(toml::from_str(&options).unwrap()).unwrap();
+        block.update().unwrap();
+        block.render().pop().map(|line| line.full_text)
+    }
+
+    #[test]
+    fn shows_the_hottest_sensor_by_default() {
+        let sysfs = sysfs("temperature-hottest");
+        assert_eq!(render(&sysfs, "").as_deref(), Some("70.0°C edge"));
+    }
+
+    #[test]
+    fn selects_sensors_by_chip_and_label() {
+        let sysfs = sysfs("temperature-select");
+        assert_eq!(
+            render(&sysfs, "chip = \"core*\"").as_deref(),
+            Some("60.0°C Package id 0")
+        );
+        assert_eq!(
+            render(&sysfs, "chip = \"coretemp\"\nlabel = \"Core ?\"").as_deref(),
+            Some("50.0°C Core 0")
+        );
+    }
+
+    #[test]
+    fn averages_the_selected_sensors() {
+        let sysfs = sysfs("temperature-average");
+        assert_eq!(
+            render(&sysfs, "aggregate = \"average\"").as_deref(),
+            Some("60.0°C edge")
+        );
+        assert_eq!(
+            render(&sysfs, "aggregate = \"average\"\nchip = \"coretemp\"").as_deref(),
+            Some("55.0°C Package id 0")
+        );
+    }
+
+    #[test]
+    fn falls_back_to_thermal_zones() {
+        let sysfs = sysfs("temperature-thermal");
+        assert_eq!(
+            render(&sysfs, "chip = \"x86_pkg_temp\"").as_deref(),
+            Some("40.0°C x86_pkg_temp")
+        );
+
+        let sysfs = FakeSysfs::new("temperature-no-hwmon");
+        sysfs
+            .write("class/thermal/thermal_zone0/type", "acpitz")
+            .write("class/thermal/thermal_zone0/temp", "45500");
+        assert_eq!(render(&sysfs, "").as_deref(), Some("45.5°C acpitz"));
+    }
+
+    #[test]
+    fn converts_to_fahrenheit() {
+        let sysfs = sysfs("temperature-fahrenheit");
+        assert_eq!(
+            render(&sysfs, "scale = \"fahrenheit\"\nlabel = \"Core 0\"").as_deref(),
+            Some("122.0°F Core 0")
+        );
+    }
+
+    #[test]
+    fn renders_nothing_without_a_matching_sensor() {
+        let sysfs = sysfs("temperature-none");
+        assert_eq!(render(&sysfs, "chip = \"nvme\""), None);
+    }
+}
@@ -0,0 +1,215 @@
+use std::convert::TryFrom;
+use std::env;
+
+use std::time::Duration;
+
+use chrono::format::{Fixed, Item, Numeric, StrftimeItems};
+use chrono::{Locale, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::de::Error as _;
+use serde::{Deserialize, Serialize};
+
+use super::{line, Block};
+use crate::error::Result;
+use crate::format::{Template, Value};
+use crate::protocol::{Align, ClickEvent, StatusLine};
+use crate::scheduler::Interval;
+
+pub const PLACEHOLDERS: &[&str] = &["time", "clocks"];
+
+/// A clock for another timezone, shown by `{clocks}`.
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Clock {
+    /// IANA name of the timezone, e.g. `America/New_York`.
+    timezone: String,
+    /// Shown before the time, the city of the timezone by default.
+    label: Option<String>,
+    /// strftime format of the time, the hours and minutes by default.
+    format: Option<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Options {
+    /// Use a 24-hour clock instead of AM/PM in the default formats.
+    hour24: bool,
+    /// strftime formats of `{time}`, a click switches to the next one.
+    formats: Option<Vec<String>>,
+    /// Locale of the day and month names, e.g. `fr_FR`. Taken from the
+    /// environment by default.
+    locale: Option<String>,
+    clocks: Vec<Clock>,
+    format: Template,
+    short_format: Option<Template>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            hour24: false,
+            formats: None,
+            locale: None,
+            clocks: vec![],
+            format: Template::parse("{time} ").unwrap(),
+            short_format: None,
+        }
+    }
+}
+
+/// Fails on strftime formats chrono cannot render.
+fn check_strftime(format: &str) -> Result<(), toml::de::Error> {
+    match StrftimeItems::new(format).any(|item| item == Item::Error) {
+        true => Err(toml::de::Error::custom(format!(
+            "invalid time format `{}`",
+            format
+        ))),
+        false => Ok(()),
+    }
+}
+
+/// Whether a strftime format shows seconds or a finer unit.
+fn shows_seconds(format: &str) -> bool {
+    StrftimeItems::new(format).any(|item| {
+        matches!(
+            item,
+            Item::Numeric(Numeric::Second, _)
+                | Item::Numeric(Numeric::Timestamp, _)
+                | Item::Numeric(Numeric::Nanosecond, _)
+                | Item::Fixed(Fixed::Nanosecond)
+                | Item::Fixed(Fixed::Nanosecond3)
+                | Item::Fixed(Fixed::Nanosecond6)
+                | Item::Fixed(Fixed::Nanosecond9)
+        )
+    })
+}
+
+/// Locale of the environment, as `LC_ALL`, `LC_TIME` or `LANG` give it, e.g.
+/// `fr_FR.UTF-8`.
+fn env_locale() -> Option<Locale> {
+    let name = ["LC_ALL", "LC_TIME", "LANG"]
+        .iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|value| !value.is_empty())?;
+    let name = name.split(['.', '@']).next()?;
+    Locale::try_from(name).ok()
+}
+
+pub struct Time {
+    /// strftime formats of `{time}`, cycled through by clicks.
+    formats: Vec<String>,
+    current: usize,
+    locale: Locale,
+    /// Other timezones along with their label and format.
+    clocks: Vec<(Tz, String, String)>,
+    /// Smallest unit shown by any of the formats.
+    tick: Duration,
+    options: Options,
+}
+
+impl Time {
+    pub fn new(options: Options) -> Result<Self, toml::de::Error> {
+        let (long, short) = match options.hour24 {
+            true => ("%a %d %b %H:%M", "%H:%M"),
+            false => ("%a %d %b %I:%M %p", "%I:%M %p"),
+        };
+        let formats = options
+            .formats
+            .clone()
+            .filter(|formats| !formats.is_empty())
+            .unwrap_or_else(|| vec![long.to_string(), short.to_string()]);
+        for format in &formats {
+            check_strftime(format)?;
+        }
+        let locale = match &options.locale {
+            Some(name) => Locale::try_from(name.as_str())
+                .map_err(|_| toml::de::Error::custom(format!("unknown locale `{}`", name)))?,
+            None => env_locale().unwrap_or(Locale::POSIX),
+        };
+        let mut clocks = vec![];
+        for clock in &options.clocks {
+            let tz: Tz = clock.timezone.parse().map_err(|_| {
+                toml::de::Error::custom(format!("unknown timezone `{}`", clock.timezone))
+            })?;
+            let format = clock.format.as_deref().unwrap_or(short).to_string();
+            check_strftime(&format)?;
+            let label = clock.label.clone().unwrap_or_else(|| {
+                let city = clock.timezone.rsplit('/').next().unwrap_or_default();
+                city.replace('_', " ")
+            });
+            clocks.push((tz, label, format));
+        }
+        let tick = match formats
+            .iter()
+            .chain(clocks.iter().map(|(_, _, format)| format))
+            .any(|format| shows_seconds(format))
+        {
+            true => Duration::from_secs(1),
+            false => Duration::from_secs(60),
+        };
+        Ok(Time {
+            formats,
+            current: 0,
+            locale,
+            clocks,
+            tick,
+            options,
+        })
+    }
+}
+
+impl Block for Time {
+    fn name(&self) -> &'static str {
+        "time"
+    }
+
+    fn color(&self) -> &'static str {
+        "cyan"
+    }
+
+    fn update(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Ticks when the smallest unit shown changes, so the displayed time
+    /// flips along with the wall clock.
+    fn interval(&self) -> Option<Interval> {
+        Some(Interval::Aligned(self.tick))
+    }
+
+    fn render(&self) -> Vec<StatusLine> {
+        let now = Utc::now();
+        let time = now
+            .with_timezone(&chrono::Local)
+            .format_localized(&self.formats[self.current], self.locale)
+            .to_string()
+            .trim_end()
+            .to_string();
+        let clocks = self
+            .clocks
+            .iter()
+            .map(|(tz, label, format)| {
+                let time = tz
+                    .from_utc_datetime(&now.naive_utc())
+                    .format_localized(format, self.locale);
+                // Some locales have no AM/PM, leaving a trailing space.
+                format!("{} {}", label, time.to_string().trim_end())
+            })
+            .collect::<Vec<_>>()
+            .join(" | ");
+        let values = [("time", Value::Text(time)), ("clocks", Value::Text(clocks))];
+
+        vec![StatusLine {
+            align: Some(Align::Right),
+            ..line(
+                &self.options.format,
+                self.options.short_format.as_ref(),
+                &values,
+            )
+        }]
+    }
+
+    fn click(&mut self, _event: &ClickEvent) {
+        self.current = (self.current + 1) % self.formats.len();
+    }
+}
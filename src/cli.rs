@@ -0,0 +1,207 @@
+use std::path::PathBuf;
+
+use log::LevelFilter;
+
+use crate::output::Format;
+
+pub const USAGE: &str = "\
+Usage: i3bar-rusty-ricer [COMMAND] [OPTIONS]
+
+Commands:
+  run            Feed the bar with status lines (default)
+  check-config   Validate the configuration and print it with its defaults
+  once           Print a single status line and exit
+  list-blocks    List the block types and their placeholders
+  preview        Show the bar in the terminal, with colors
+  msg <COMMAND>  Send a command to the running bar, see below
+
+Options:
+  -c, --config <PATH>      Configuration file to use
+  -o, --output <NAME>      i3bar, swaybar, lemonbar, polybar, plain or terminal
+  -l, --log-level <LEVEL>  error, warn, info, debug, trace or off
+  -h, --help               Print this help
+
+Commands of `msg`, where BLOCK is a block type, or `type#n` for the n-th
+block of that type only:";
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum Command {
+    #[default]
+    Run,
+    CheckConfig,
+    Once,
+    ListBlocks,
+    Preview,
+    /// Sends the given line to the running bar.
+    Msg(String),
+    Help,
+}
+
+/// Command-line arguments.
+#[derive(Debug, Default)]
+pub struct Args {
+    pub command: Command,
+    pub config: Option<PathBuf>,
+    pub output: Format,
+    /// Overrides the level set in the configuration.
+    pub log_level: Option<LevelFilter>,
+}
+
+impl Args {
+    pub fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Args, String> {
+        let mut parsed = Args::default();
+        let mut command = None;
+        while let Some(arg) = args.next() {
+            // Options take their value either as `--option=value` or as the
+            // next argument.
+            let (option, mut value) = match arg.split_once('=') {
+                Some((option, value)) if option.starts_with("--") => {
+                    (option.to_string(), Some(value.to_string()))
+                }
+                _ => (arg, None),
+            };
+            let mut value = |what: &str| match value.take().or_else(|| args.next()) {
+                Some(value) => Ok(value),
+                None => Err(format!("`{}` expects {}", option, what)),
+            };
+            match option.as_str() {
+                "-c" | "--config" => parsed.config = Some(PathBuf::from(value("a path")?)),
+                "-o" | "--output" => parsed.output = value("an output name")?.parse()?,
+                "-l" | "--log-level" => {
+                    let level = value("a log level")?;
+                    let level = level
+                        .parse()
+                        .map_err(|_| format!("unknown log level `{}`", level))?;
+                    parsed.log_level = Some(level);
+                }
+                "-h" | "--help" => parsed.command = Command::Help,
+                // Everything after `msg` makes up the command sent to the bar.
+                "msg" if command.is_none() => {
+                    let message: Vec<String> = args.by_ref().collect();
+                    command = Some(Command::Msg(message.join(" ")));
+                }
+                name if command.is_none() && !name.starts_with('-') => {
+                    command = Some(match name {
+                        "run" => Command::Run,
+                        "check-config" => Command::CheckConfig,
+                        "once" => Command::Once,
+                        "list-blocks" => Command::ListBlocks,
+                        "preview" => Command::Preview,
+                        "help" => Command::Help,
+                        _ => return Err(format!("unknown command `{}`", name)),
+                    });
+                }
+                _ => return Err(format!("unexpected argument `{}`", option)),
+            }
+        }
+        if parsed.command != Command::Help {
+            parsed.command = command.unwrap_or_default();
+        }
+        Ok(parsed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        Args::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn runs_by_default() {
+        let args = parse(&[]).unwrap();
+        assert_eq!(args.command, Command::Run);
+        assert_eq!(args.config, None);
+        assert_eq!(args.output, Format::I3bar);
+        assert_eq!(args.log_level, None);
+    }
+
+    #[test]
+    fn takes_values_after_an_equal_sign_or_as_the_next_argument() {
+        for args in [
+            &[
+                "--config=/tmp/bar.toml",
+                "--output=plain",
+                "--log-level=debug",
+            ][..],
+            &["--config", "/tmp/bar.toml", "-o", "plain", "-l", "debug"],
+        ] {
+            let args = parse(args).unwrap();
+            assert_eq!(args.config, Some(PathBuf::from("/tmp/bar.toml")));
+            assert_eq!(args.output, Format::Plain);
+            assert_eq!(args.log_level, Some(LevelFilter::Debug));
+        }
+    }
+
+    #[test]
+    fn rejects_missing_and_invalid_values() {
+        assert_eq!(
+            parse(&["--config"]).unwrap_err(),
+            "`--config` expects a path"
+        );
+        assert_eq!(
+            parse(&["run", "-o"]).unwrap_err(),
+            "`-o` expects an output name"
+        );
+        assert!(parse(&["--output=dzen"]).is_err());
+        assert_eq!(
+            parse(&["-l", "loud"]).unwrap_err(),
+            "unknown log level `loud`"
+        );
+    }
+
+    #[test]
+    fn picks_the_command() {
+        assert_eq!(
+            parse(&["once", "-o", "plain"]).unwrap().command,
+            Command::Once
+        );
+        assert_eq!(
+            parse(&["-c", "bar.toml", "check-config"]).unwrap().command,
+            Command::CheckConfig
+        );
+        assert_eq!(
+            parse(&["list-blocks"]).unwrap().command,
+            Command::ListBlocks
+        );
+    }
+
+    #[test]
+    fn help_wins_over_the_command() {
+        assert_eq!(parse(&["once", "-h"]).unwrap().command, Command::Help);
+        assert_eq!(
+            parse(&["--help", "preview"]).unwrap().command,
+            Command::Help
+        );
+        assert_eq!(parse(&["help"]).unwrap().command, Command::Help);
+    }
+
+    #[test]
+    fn msg_takes_the_rest_of_the_arguments() {
+        assert_eq!(
+            parse(&["-c", "bar.toml", "msg", "set-text", "cpu", "-h", "hi"])
+                .unwrap()
+                .command,
+            Command::Msg("set-text cpu -h hi".to_string())
+        );
+        assert_eq!(
+            parse(&["msg"]).unwrap().command,
+            Command::Msg(String::new())
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_commands_and_options() {
+        assert_eq!(parse(&["start"]).unwrap_err(), "unknown command `start`");
+        assert_eq!(
+            parse(&["run", "once"]).unwrap_err(),
+            "unexpected argument `once`"
+        );
+        assert_eq!(
+            parse(&["--verbose"]).unwrap_err(),
+            "unexpected argument `--verbose`"
+        );
+    }
+}
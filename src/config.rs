@@ -0,0 +1,146 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::blocks;
+use crate::logging::LogConfig;
+use crate::scheduler::Interval;
+use crate::theme::{self, Theme};
+
+pub const APP_NAME: &str = "i3bar-rusty-ricer";
+
+/// The whole bar configuration, as read from `config.toml`.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Update interval of blocks that do not set their own.
+    #[serde(default = "default_interval")]
+    pub interval: Interval,
+    /// Name of a built-in theme, or of a theme file.
+    #[serde(default = "default_theme")]
+    pub theme: String,
+    #[serde(default)]
+    pub log: LogConfig,
+    #[serde(default, rename = "block")]
+    pub blocks: Vec<BlockConfig>,
+    /// Directory of the configuration file, where themes are looked up.
+    #[serde(skip)]
+    pub dir: Option<PathBuf>,
+}
+
+/// One `[[block]]` entry. Keys other than the common ones are handed over to
+/// the block type itself.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct BlockConfig {
+    pub block: String,
+    pub interval: Option<Interval>,
+    pub color: Option<String>,
+    /// Overrides of the bar theme for this block only.
+    pub theme: Option<Theme>,
+    #[serde(flatten)]
+    pub options: toml::value::Table,
+}
+
+fn default_interval() -> Interval {
+    Interval::from_secs(2)
+}
+
+fn default_theme() -> String {
+    theme::DEFAULT_THEME.to_string()
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            interval: default_interval(),
+            theme: default_theme(),
+            log: LogConfig::default(),
+            blocks: blocks::BLOCK_NAMES
+                .iter()
+                .map(|name| BlockConfig::new(name))
+                .collect(),
+            dir: default_path().and_then(|path| Some(path.parent()?.to_path_buf())),
+        }
+    }
+}
+
+impl BlockConfig {
+    pub fn new(block: &str) -> Self {
+        BlockConfig {
+            block: block.to_string(),
+            interval: None,
+            color: None,
+            theme: None,
+            options: toml::value::Table::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Loads the configuration from `path`, or from the default location when
+    /// `None`. A missing default file is not an error, the built-in
+    /// configuration is used instead.
+    pub fn load(path: Option<&Path>) -> Result<Config, Error> {
+        let (path, explicit) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Config::default()),
+            },
+        };
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(err) if !explicit && err.kind() == io::ErrorKind::NotFound => {
+                return Ok(Config::default())
+            }
+            Err(err) => return Err(Error::Io(path, err)),
+        };
+        let mut config: Config =
+            toml::from_str(&content).map_err(|err| Error::Parse(path.clone(), err))?;
+        config.dir = path.parent().map(Path::to_path_buf);
+        Ok(config)
+    }
+}
+
+/// `$XDG_CONFIG_HOME/i3bar-rusty-ricer/config.toml`, falling back to
+/// `~/.config` when `XDG_CONFIG_HOME` is unset.
+pub fn default_path() -> Option<PathBuf> {
+    let base = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+    Some(base.join(APP_NAME).join("config.toml"))
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+    Theme(String),
+    /// Options of the block at `index` (0-based) could not be understood.
+    Block {
+        index: usize,
+        block: String,
+        source: toml::de::Error,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(path, err) => write!(f, "cannot read {}: {}", path.display(), err),
+            Error::Parse(path, err) => write!(f, "invalid config {}: {}", path.display(), err),
+            Error::Theme(err) => f.write_str(err),
+            Error::Block {
+                index,
+                block,
+                source,
+            } => write!(f, "block #{} (`{}`): {}", index + 1, block, source),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
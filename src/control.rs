@@ -0,0 +1,271 @@
+//! Control socket letting other programs, e.g. i3 keybindings, poke the
+//! running bar through `i3bar-rusty-ricer msg`.
+//!
+//! A client sends a single command line and gets a single line back, `ok` or
+//! `error: <reason>`.
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+use std::time::Duration;
+
+use crate::config::APP_NAME;
+use crate::error::{Error, Result};
+use crate::event::Event;
+use crate::threshold::State;
+
+/// How long a client has to send its command.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub const COMMANDS: &str = "\
+refresh [BLOCK]          update a block right away, or every block
+set-text BLOCK [TEXT]    show TEXT in place of a block, or its own text again
+set-state BLOCK [STATE]  force the state of a block (idle, info, good,
+                         warning or critical), or let it pick its own again
+toggle BLOCK             hide or show a block
+reload                   read the configuration again";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// Refreshes the blocks of the given type, or every block.
+    Refresh(Option<String>),
+    /// Replaces the text of the blocks of the given type, `None` to restore
+    /// their own.
+    SetText(String, Option<String>),
+    SetState(String, Option<State>),
+    Toggle(String),
+    Reload,
+}
+
+impl FromStr for Command {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Command, String> {
+        let line = line.trim();
+        let (name, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let (block, argument) = rest.trim().split_once(' ').unwrap_or((rest.trim(), ""));
+        let block = match block {
+            "" => None,
+            block => Some(block.to_string()),
+        };
+        let argument = match argument.trim() {
+            "" => None,
+            argument => Some(argument.to_string()),
+        };
+        let block_required = || block.clone().ok_or(format!("`{}` expects a block", name));
+        match name {
+            "refresh" => Ok(Command::Refresh(block)),
+            "set-text" => Ok(Command::SetText(block_required()?, argument)),
+            "set-state" => {
+                let state = match argument.as_deref() {
+                    None => None,
+                    Some("idle") => Some(State::Idle),
+                    Some("info") => Some(State::Info),
+                    Some("good") => Some(State::Good),
+                    Some("warning") => Some(State::Warning),
+                    Some("critical") => Some(State::Critical),
+                    Some(state) => return Err(format!("unknown state `{}`", state)),
+                };
+                Ok(Command::SetState(block_required()?, state))
+            }
+            "toggle" => Ok(Command::Toggle(block_required()?)),
+            "reload" => Ok(Command::Reload),
+            "" => Err("empty command".to_string()),
+            _ => Err(format!("unknown command `{}`", name)),
+        }
+    }
+}
+
+/// A command along with where to send the answer, `Ok` or the reason it
+/// failed.
+#[derive(Debug)]
+pub struct Request {
+    pub command: Command,
+    pub reply: Sender<Result<(), String>>,
+}
+
+/// `$XDG_RUNTIME_DIR/i3bar-rusty-ricer.sock`, or a socket in the temporary
+/// directory named after the user when `XDG_RUNTIME_DIR` is unset.
+pub fn socket_path() -> PathBuf {
+    match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir).join(format!("{}.sock", APP_NAME)),
+        _ => std::env::temp_dir().join(format!("{}-{}.sock", APP_NAME, uid())),
+    }
+}
+
+fn uid() -> u32 {
+    // SAFETY: getuid cannot fail and has no preconditions.
+    unsafe { libc::getuid() }
+}
+
+/// Fails when the file at `path` belongs to another user, who could then
+/// eavesdrop on or answer in place of the bar, e.g. in a shared `/tmp`.
+fn check_owner(path: &Path) -> io::Result<()> {
+    let owner = std::fs::symlink_metadata(path)?.uid();
+    match owner == uid() {
+        true => Ok(()),
+        false => Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{} belongs to another user", path.display()),
+        )),
+    }
+}
+
+/// Listens on the control socket, forwarding commands to the main loop.
+/// Fails when another bar already listens on it.
+pub fn spawn_listener(tx: Sender<Event>) -> io::Result<PathBuf> {
+    let path = socket_path();
+    if path.symlink_metadata().is_ok() {
+        check_owner(&path)?;
+        if UnixStream::connect(&path).is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                format!("another bar listens on {}", path.display()),
+            ));
+        }
+        // Left behind by a bar that did not exit cleanly.
+        std::fs::remove_file(&path)?;
+    }
+    let listener = UnixListener::bind(&path)?;
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            // A client slow to send its command must not hold up the others.
+            let tx = tx.clone();
+            thread::spawn(move || match serve(stream, &tx) {
+                Ok(()) => {}
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                    log::warn!("a client sent no command in time")
+                }
+                Err(err) => log::warn!("cannot answer a client: {}", err),
+            });
+        }
+    });
+    Ok(path)
+}
+
+/// Answers the single command of a client.
+fn serve(stream: UnixStream, tx: &Sender<Event>) -> io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut line = String::new();
+    // Other bars connect without a word to check whether this one runs.
+    if BufReader::new(&stream).read_line(&mut line)? == 0 {
+        return Ok(());
+    }
+    let answer = match line.parse::<Command>() {
+        Ok(command) => {
+            log::debug!("control command {:?}", command);
+            let (reply, answer) = mpsc::channel();
+            let _ = tx.send(Event::Control(Request { command, reply }));
+            answer
+                .recv()
+                .unwrap_or_else(|_| Err("the bar is shutting down".to_string()))
+        }
+        Err(err) => Err(err),
+    };
+    let mut stream = stream;
+    match answer {
+        Ok(()) => writeln!(stream, "ok"),
+        Err(err) => writeln!(stream, "error: {}", err),
+    }
+}
+
+/// Sends `command` to the running bar, returning its answer.
+pub fn send(command: &str) -> Result<()> {
+    // Checked here too so typos are reported even without a running bar.
+    command.parse::<Command>().map_err(Error::Args)?;
+    let path = socket_path();
+    let mut stream = UnixStream::connect(&path).map_err(|err| {
+        Error::Data(format!(
+            "cannot reach the bar on {}: {}",
+            path.display(),
+            err
+        ))
+    })?;
+    check_owner(&path).map_err(|err| Error::Data(format!("cannot reach the bar: {}", err)))?;
+    writeln!(stream, "{}", command)?;
+    let mut answer = String::new();
+    BufReader::new(&stream).read_line(&mut answer)?;
+    match answer.trim() {
+        "ok" => Ok(()),
+        answer => Err(Error::Data(
+            answer.strip_prefix("error: ").unwrap_or(answer).to_string(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> Result<Command, String> {
+        line.parse()
+    }
+
+    #[test]
+    fn parses_refresh() {
+        assert_eq!(parse("refresh"), Ok(Command::Refresh(None)));
+        assert_eq!(
+            parse("refresh disk#2\n"),
+            Ok(Command::Refresh(Some("disk#2".to_string())))
+        );
+    }
+
+    #[test]
+    fn keeps_spaces_in_the_text() {
+        assert_eq!(
+            parse("set-text cpu hello world"),
+            Ok(Command::SetText(
+                "cpu".to_string(),
+                Some("hello world".to_string())
+            ))
+        );
+        assert_eq!(
+            parse("set-text cpu"),
+            Ok(Command::SetText("cpu".to_string(), None))
+        );
+        assert!(parse("set-text").is_err());
+    }
+
+    #[test]
+    fn parses_states() {
+        assert_eq!(
+            parse("set-state battery critical"),
+            Ok(Command::SetState(
+                "battery".to_string(),
+                Some(State::Critical)
+            ))
+        );
+        assert_eq!(
+            parse("set-state battery"),
+            Ok(Command::SetState("battery".to_string(), None))
+        );
+        assert_eq!(
+            parse("set-state battery red"),
+            Err("unknown state `red`".to_string())
+        );
+    }
+
+    #[test]
+    fn parses_toggle_and_reload() {
+        assert_eq!(
+            parse("toggle network"),
+            Ok(Command::Toggle("network".to_string()))
+        );
+        assert_eq!(parse("toggle"), Err("`toggle` expects a block".to_string()));
+        assert_eq!(parse(" reload "), Ok(Command::Reload));
+    }
+
+    #[test]
+    fn rejects_empty_and_unknown_commands() {
+        assert_eq!(parse(""), Err("empty command".to_string()));
+        assert_eq!(parse("  \n"), Err("empty command".to_string()));
+        assert_eq!(
+            parse("restart cpu"),
+            Err("unknown command `restart`".to_string())
+        );
+    }
+}
@@ -0,0 +1,66 @@
+//! Errors of the bar as a whole, from a bad command line to a block that
+//! cannot read its data.
+
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use crate::config;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Invalid command line.
+    Args(String),
+    Config(config::Error),
+    /// A file a block gets its data from could not be read.
+    Read(PathBuf, io::Error),
+    /// A block got data it cannot make sense of.
+    Data(String),
+    /// Writing the status lines, or setting up the threads feeding the bar.
+    Io(io::Error),
+    Json(serde_json::Error),
+}
+
+impl Error {
+    /// Whether i3bar went away, closing our stdout.
+    pub fn is_broken_pipe(&self) -> bool {
+        match self {
+            Error::Io(err) => err.kind() == io::ErrorKind::BrokenPipe,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Args(err) | Error::Data(err) => f.write_str(err),
+            Error::Config(err) => err.fmt(f),
+            Error::Read(path, err) => write!(f, "cannot read {}: {}", path.display(), err),
+            Error::Io(err) => err.fmt(f),
+            Error::Json(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<config::Error> for Error {
+    fn from(err: config::Error) -> Self {
+        Error::Config(err)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Error::Json(err)
+    }
+}
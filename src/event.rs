@@ -0,0 +1,67 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+
+use crate::control::Request;
+use crate::protocol::ClickEvent;
+
+/// Everything that can wake the main loop before its next refresh.
+#[derive(Debug)]
+pub enum Event {
+    Click(ClickEvent),
+    /// The block at this index has new data to display.
+    Update(usize),
+    /// i3bar hid the bar, stop producing output.
+    Stop,
+    /// The bar is visible again.
+    Continue,
+    /// A command received on the control socket.
+    Control(Request),
+}
+
+/// Handed to a block so its own threads can ask for it to be updated as soon
+/// as something happens, instead of waiting for its next interval.
+#[derive(Clone)]
+pub struct Notifier {
+    index: usize,
+    tx: Sender<Event>,
+    /// Cleared once the bar the block belongs to is gone, e.g. replaced on
+    /// reload, as the index would then point at another block.
+    active: Arc<AtomicBool>,
+    /// Set while i3bar has the bar hidden.
+    paused: Arc<AtomicBool>,
+}
+
+impl Notifier {
+    pub fn new(
+        index: usize,
+        tx: Sender<Event>,
+        active: Arc<AtomicBool>,
+        paused: Arc<AtomicBool>,
+    ) -> Self {
+        Notifier {
+            index,
+            tx,
+            active,
+            paused,
+        }
+    }
+
+    /// Whether the bar is hidden, threads polling for changes had better
+    /// wait then.
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    /// Whether the block is still part of the bar, threads should exit
+    /// otherwise.
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    /// Returns `false` once the block is no longer part of the bar or the
+    /// bar has stopped listening, threads should exit then.
+    pub fn notify(&self) -> bool {
+        self.is_active() && self.tx.send(Event::Update(self.index)).is_ok()
+    }
+}
@@ -0,0 +1,436 @@
+//! Templates used by blocks to build their text, e.g.
+//! `" : {used:.1Gi} / {total:.1Gi}"`.
+//!
+//! A placeholder is a name between braces, optionally followed by a
+//! specifier after a colon: `[[fill]align][width][.precision][unit]`, where
+//! `align` is one of `<`, `^`, `>`, and `unit` a byte unit as understood by
+//! `Unit::parse`. Literal braces are written `{{` and `}}`.
+
+use serde::de::{self, Deserialize, Deserializer};
+use serde::{Serialize, Serializer};
+
+use crate::units::{self, Unit, Units};
+
+/// What a placeholder gets replaced with.
+#[derive(Debug, Clone)]
+pub enum Value {
+    Text(String),
+    Number(f64),
+    /// A size in bytes, scaled to the unit of the placeholder, or to the
+    /// best fitting unit of the given system.
+    Bytes(f64, Units),
+}
+
+const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// One bar glyph per percentage, from `▁` for 0 to `█` for 100.
+pub fn sparkline<I: IntoIterator<Item = f64>>(percents: I) -> String {
+    percents
+        .into_iter()
+        .map(|percent| {
+            let step = (percent.clamp(0.0, 100.0) / 100.0 * (BARS.len() - 1) as f64).round();
+            BARS[step as usize]
+        })
+        .collect()
+}
+
+/// Human-readable duration keeping its two largest units, e.g. `3d 4h` or
+/// `12m 5s`.
+pub fn duration(secs: u64) -> String {
+    let units = [
+        (secs / 86400, "d"),
+        (secs / 3600 % 24, "h"),
+        (secs / 60 % 60, "m"),
+        (secs % 60, "s"),
+    ];
+    let largest = units.iter().position(|&(n, _)| n > 0).unwrap_or(3);
+    units[largest..]
+        .iter()
+        .take(2)
+        .enumerate()
+        .filter(|&(i, &(n, _))| i == 0 || n > 0)
+        .map(|(_, (n, unit))| format!("{}{}", n, unit))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Spec {
+    fill: Option<char>,
+    align: Option<Align>,
+    width: Option<usize>,
+    precision: Option<usize>,
+    unit: Option<Unit>,
+}
+
+impl Spec {
+    fn parse(spec: &str) -> Result<Spec, String> {
+        let mut parsed = Spec::default();
+        let chars: Vec<char> = spec.chars().collect();
+        let mut i = 0;
+
+        let align = |c: char| match c {
+            '<' => Some(Align::Left),
+            '^' => Some(Align::Center),
+            '>' => Some(Align::Right),
+            _ => None,
+        };
+        if chars.len() >= 2 && align(chars[1]).is_some() {
+            parsed.fill = Some(chars[0]);
+            parsed.align = align(chars[1]);
+            i = 2;
+        } else if let Some(a) = chars.first().and_then(|&c| align(c)) {
+            parsed.align = Some(a);
+            i = 1;
+        }
+
+        let digits = |i: &mut usize| {
+            let start = *i;
+            while *i < chars.len() && chars[*i].is_ascii_digit() {
+                *i += 1;
+            }
+            chars[start..*i].iter().collect::<String>().parse().ok()
+        };
+        parsed.width = digits(&mut i);
+        if chars.get(i) == Some(&'.') {
+            i += 1;
+            parsed.precision = digits(&mut i);
+            if parsed.precision.is_none() {
+                return Err(format!("missing precision after `.` in `{}`", spec));
+            }
+        }
+
+        let unit: String = chars[i..].iter().collect();
+        if !unit.is_empty() {
+            parsed.unit = match Unit::parse(&unit) {
+                Some(unit) => Some(unit),
+                None => return Err(format!("unknown unit `{}` in `{}`", unit, spec)),
+            };
+        }
+        Ok(parsed)
+    }
+
+    fn apply(&self, value: &Value) -> String {
+        let (text, default_align) = match value {
+            Value::Text(text) => {
+                let text = match self.precision {
+                    Some(precision) => text.chars().take(precision).collect(),
+                    None => text.clone(),
+                };
+                (text, Align::Left)
+            }
+            Value::Number(number) => {
+                let text = match self.precision {
+                    Some(precision) => format!("{:.*}", precision, number),
+                    None => number.to_string(),
+                };
+                (text, Align::Right)
+            }
+            Value::Bytes(bytes, system) => {
+                let unit = self.unit.unwrap_or_else(|| Unit::auto(*bytes, *system));
+                let text = units::format_bytes(*bytes, unit, self.precision.unwrap_or(1));
+                (text, Align::Right)
+            }
+        };
+        pad(
+            text,
+            self.width.unwrap_or(0),
+            self.fill.unwrap_or(' '),
+            self.align.unwrap_or(default_align),
+        )
+    }
+}
+
+fn pad(text: String, width: usize, fill: char, align: Align) -> String {
+    let len = text.chars().count();
+    if len >= width {
+        return text;
+    }
+    let missing = width - len;
+    let (left, right) = match align {
+        Align::Left => (0, missing),
+        Align::Right => (missing, 0),
+        Align::Center => (missing / 2, missing - missing / 2),
+    };
+    let mut padded = String::with_capacity(text.len() + missing);
+    padded.extend(std::iter::repeat_n(fill, left));
+    padded.push_str(&text);
+    padded.extend(std::iter::repeat_n(fill, right));
+    padded
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Literal(String),
+    Placeholder { name: String, spec: Spec },
+}
+
+/// A parsed format string.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    /// The format string the template was parsed from.
+    source: String,
+    segments: Vec<Segment>,
+}
+
+impl Template {
+    pub fn parse(template: &str) -> Result<Template, String> {
+        let mut segments = vec![];
+        let mut literal = String::new();
+        let mut chars = template.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut placeholder = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => placeholder.push(c),
+                            None => return Err(format!("unclosed `{{` in `{}`", template)),
+                        }
+                    }
+                    let (name, spec) = match placeholder.find(':') {
+                        Some(colon) => (&placeholder[..colon], &placeholder[colon + 1..]),
+                        None => (placeholder.as_str(), ""),
+                    };
+                    if name.is_empty()
+                        || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+                    {
+                        return Err(format!("invalid placeholder `{{{}}}`", placeholder));
+                    }
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(Segment::Placeholder {
+                        name: name.to_string(),
+                        spec: Spec::parse(spec)?,
+                    });
+                }
+                '}' => return Err(format!("unmatched `}}` in `{}`", template)),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+        Ok(Template {
+            source: template.to_string(),
+            segments,
+        })
+    }
+
+    /// Fails on the first placeholder that is not one of `names`.
+    pub fn check(&self, names: &[&str]) -> Result<(), String> {
+        for segment in &self.segments {
+            if let Segment::Placeholder { name, .. } = segment {
+                if !names.contains(&name.as_str()) {
+                    return Err(format!(
+                        "unknown placeholder `{{{}}}`, expected one of {}",
+                        name,
+                        names
+                            .iter()
+                            .map(|name| format!("{{{}}}", name))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Replaces placeholders with their value, placeholders missing from
+    /// `values` are left empty.
+    pub fn render(&self, values: &[(&str, Value)]) -> String {
+        let mut text = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(literal) => text.push_str(literal),
+                Segment::Placeholder { name, spec } => {
+                    if let Some((_, value)) = values.iter().find(|(key, _)| key == name) {
+                        text.push_str(&spec.apply(value));
+                    }
+                }
+            }
+        }
+        text
+    }
+}
+
+/// Written back as the format string it was parsed from.
+impl Serialize for Template {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.source)
+    }
+}
+
+impl<'de> Deserialize<'de> for Template {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let template = String::deserialize(deserializer)?;
+        Template::parse(&template).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(template: &str, values: &[(&str, Value)]) -> String {
+        Template::parse(template).unwrap().render(values)
+    }
+
+    #[test]
+    fn parses_specs() {
+        assert_eq!(Spec::parse("").unwrap(), Spec::default());
+        assert_eq!(
+            Spec::parse("*^10.2Gi").unwrap(),
+            Spec {
+                fill: Some('*'),
+                align: Some(Align::Center),
+                width: Some(10),
+                precision: Some(2),
+                unit: Unit::parse("Gi"),
+            }
+        );
+        assert_eq!(
+            Spec::parse(">5").unwrap(),
+            Spec {
+                align: Some(Align::Right),
+                width: Some(5),
+                ..Default::default()
+            }
+        );
+        // A fill is only taken as such when followed by an alignment.
+        assert_eq!(
+            Spec::parse("0>3").unwrap(),
+            Spec {
+                fill: Some('0'),
+                align: Some(Align::Right),
+                width: Some(3),
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            Spec::parse(".1M").unwrap(),
+            Spec {
+                precision: Some(1),
+                unit: Unit::parse("M"),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_specs() {
+        assert!(Spec::parse("5.").is_err());
+        assert!(Spec::parse(".1X").is_err());
+        assert!(Spec::parse("Bi").is_err());
+    }
+
+    #[test]
+    fn parses_templates() {
+        let template = Template::parse("a {x} b {y:.1}").unwrap();
+        assert_eq!(
+            template.segments,
+            [
+                Segment::Literal("a ".to_string()),
+                Segment::Placeholder {
+                    name: "x".to_string(),
+                    spec: Spec::default(),
+                },
+                Segment::Literal(" b ".to_string()),
+                Segment::Placeholder {
+                    name: "y".to_string(),
+                    spec: Spec {
+                        precision: Some(1),
+                        ..Default::default()
+                    },
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_templates() {
+        assert!(Template::parse("{x").is_err());
+        assert!(Template::parse("x}").is_err());
+        assert!(Template::parse("{}").is_err());
+        assert!(Template::parse("{a b}").is_err());
+        assert!(Template::parse("{x:.}").is_err());
+    }
+
+    #[test]
+    fn escapes_braces() {
+        assert_eq!(render("{{x}} }}{{", &[("x", Value::Number(1.0))]), "{x} }{");
+        assert_eq!(render("{{{x}}}", &[("x", Value::Number(1.0))]), "{1}");
+    }
+
+    #[test]
+    fn checks_placeholders() {
+        let template = Template::parse("{a} {b}").unwrap();
+        assert!(template.check(&["a", "b", "c"]).is_ok());
+        assert!(template.check(&["a"]).is_err());
+    }
+
+    #[test]
+    fn renders_numbers_and_text() {
+        let values = [
+            ("n", Value::Number(1.23456)),
+            ("t", Value::Text("wlan0".to_string())),
+        ];
+        assert_eq!(render("{n:.2}", &values), "1.23");
+        assert_eq!(render("[{n:6.1}]", &values), "[   1.2]");
+        assert_eq!(render("[{n:<6.1}]", &values), "[1.2   ]");
+        assert_eq!(render("[{t:6}]", &values), "[wlan0 ]");
+        assert_eq!(render("[{t:>6}]", &values), "[ wlan0]");
+        assert_eq!(render("[{t:-^9}]", &values), "[--wlan0--]");
+        assert_eq!(render("[{t:.2}]", &values), "[wl]");
+        assert_eq!(render("[{t:2}]", &values), "[wlan0]");
+        // Placeholders without a value are left empty.
+        assert_eq!(render("[{missing}]", &values), "[]");
+    }
+
+    #[test]
+    fn renders_bytes() {
+        let values = [
+            ("si", Value::Bytes(1_500_000_000.0, Units::Si)),
+            ("bin", Value::Bytes(1536.0 * 1024.0, Units::Binary)),
+            ("small", Value::Bytes(512.0, Units::Binary)),
+        ];
+        assert_eq!(render("{si}", &values), "1.5GB");
+        assert_eq!(render("{si:.2M}", &values), "1500.00MB");
+        assert_eq!(render("{bin}", &values), "1.5MiB");
+        assert_eq!(render("{bin:.0Ki}", &values), "1536KiB");
+        assert_eq!(render("{small:.3}", &values), "512B");
+        assert_eq!(render("{small:>6}", &values), "  512B");
+    }
+
+    #[test]
+    fn formats_durations() {
+        assert_eq!(duration(0), "0s");
+        assert_eq!(duration(65), "1m 5s");
+        assert_eq!(duration(3600), "1h");
+        assert_eq!(duration(3 * 86400 + 4 * 3600 + 59), "3d 4h");
+    }
+
+    #[test]
+    fn draws_sparklines() {
+        assert_eq!(sparkline([0.0, 50.0, 100.0, 150.0]), "▁▅██");
+    }
+}
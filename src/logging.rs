@@ -0,0 +1,188 @@
+//! Logging to stderr, which i3 keeps in its own log, and to a file rotated
+//! once it grows too large. Stdout is left to the i3bar protocol.
+
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use log::{LevelFilter, Log, Metadata, Record};
+use serde::{Deserialize, Serialize};
+
+use crate::config::APP_NAME;
+
+/// Size beyond which the log file is rotated.
+const MAX_FILE_SIZE: u64 = 1024 * 1024;
+/// Rotated files kept along the current one, as `<file>.1`, `<file>.2`, ...
+const ROTATED_FILES: usize = 3;
+
+/// Target prefix of the logs of blocks, which are named after their module.
+const BLOCKS_TARGET: &str = concat!(env!("CARGO_CRATE_NAME"), "::blocks::");
+
+/// The `[log]` table of the configuration.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    pub level: LevelFilter,
+    /// Levels of the blocks of a given type, e.g. `{ cpu = "debug" }`.
+    pub blocks: HashMap<String, LevelFilter>,
+    pub stderr: bool,
+    /// Whether to also log to `path`.
+    pub file: bool,
+    /// Log file, `$XDG_STATE_HOME/i3bar-rusty-ricer/i3bar-rusty-ricer.log` by
+    /// default.
+    pub path: Option<PathBuf>,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            level: LevelFilter::Warn,
+            blocks: HashMap::new(),
+            stderr: true,
+            file: false,
+            path: None,
+        }
+    }
+}
+
+/// Target used for the logs about the block named `block`, matching the
+/// target of the logs made from within its module.
+pub fn block_target(block: &str) -> String {
+    format!("{}{}", BLOCKS_TARGET, block)
+}
+
+/// `$XDG_STATE_HOME/i3bar-rusty-ricer/i3bar-rusty-ricer.log`, falling back to
+/// `~/.local/state` when `XDG_STATE_HOME` is unset.
+pub fn default_path() -> Option<PathBuf> {
+    let base = match std::env::var_os("XDG_STATE_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".local/state"),
+    };
+    Some(base.join(APP_NAME).join(format!("{}.log", APP_NAME)))
+}
+
+/// A log file along with its current size.
+struct LogFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+}
+
+impl LogFile {
+    fn open(path: PathBuf) -> io::Result<LogFile> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(LogFile { path, file, size })
+    }
+
+    fn write(&mut self, line: &str) -> io::Result<()> {
+        if self.size + line.len() as u64 > MAX_FILE_SIZE {
+            self.rotate()?;
+        }
+        self.file.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    /// Shifts `<file>.N` to `<file>.N+1`, dropping the oldest, then starts
+    /// over with an empty file.
+    fn rotate(&mut self) -> io::Result<()> {
+        let rotated = |n: usize| {
+            let mut name = self.path.clone().into_os_string();
+            name.push(format!(".{}", n));
+            PathBuf::from(name)
+        };
+        for n in (1..ROTATED_FILES).rev() {
+            let _ = fs::rename(rotated(n), rotated(n + 1));
+        }
+        fs::rename(&self.path, rotated(1))?;
+        *self = LogFile::open(self.path.clone())?;
+        Ok(())
+    }
+}
+
+struct Logger {
+    level: LevelFilter,
+    blocks: HashMap<String, LevelFilter>,
+    stderr: bool,
+    file: Option<Mutex<LogFile>>,
+}
+
+impl Logger {
+    fn level(&self, target: &str) -> LevelFilter {
+        target
+            .strip_prefix(BLOCKS_TARGET)
+            .and_then(|block| self.blocks.get(block))
+            .copied()
+            .unwrap_or(self.level)
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let target = record.target();
+        let source = target
+            .strip_prefix(BLOCKS_TARGET)
+            .or_else(|| target.strip_prefix(concat!(env!("CARGO_CRATE_NAME"), "::")))
+            .unwrap_or(target);
+        let message = format!("{} {}: {}", record.level(), source, record.args());
+        if self.stderr {
+            eprintln!("{}: {}", APP_NAME, message);
+        }
+        if let Some(file) = &self.file {
+            let line = format!(
+                "{} {}\n",
+                chrono::Local::now().format("%Y-%m-%dT%H:%M:%S%.3f"),
+                message
+            );
+            if let Ok(mut file) = file.lock() {
+                let _ = file.write(&line);
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = &self.file {
+            if let Ok(mut file) = file.lock() {
+                let _ = file.file.flush();
+            }
+        }
+    }
+}
+
+/// Installs the logger described by `config`, failing when the log file
+/// cannot be opened.
+pub fn init(config: &LogConfig) -> io::Result<()> {
+    let file = match (config.file, config.path.clone().or_else(default_path)) {
+        (true, Some(path)) => Some(Mutex::new(LogFile::open(path.clone()).map_err(|err| {
+            io::Error::new(
+                err.kind(),
+                format!("cannot open log file {}: {}", path.display(), err),
+            )
+        })?)),
+        _ => None,
+    };
+    let max_level = config.blocks.values().copied().fold(config.level, Ord::max);
+    let logger = Logger {
+        level: config.level,
+        blocks: config.blocks.clone(),
+        stderr: config.stderr,
+        file,
+    };
+    log::set_max_level(max_level);
+    // Only fails when a logger is already installed.
+    let _ = log::set_boxed_logger(Box::new(logger));
+    Ok(())
+}
@@ -1,222 +1,233 @@
-use std::fmt::format;
-#[macro_use]
-use std::io::{self, Write};
-use std::thread;
-use std::time;
+mod blocks;
+mod cli;
+mod config;
+mod control;
+mod error;
+mod event;
+mod format;
+mod logging;
+mod output;
+mod protocol;
+mod scheduler;
+mod signals;
+mod theme;
+mod threshold;
+mod units;
 
-use chrono::{Datelike, Timelike};
-use serde::{Deserialize, Serialize};
-use sysinfo::{DiskExt, NetworkExt, ProcessorExt, SystemExt};
-use systemstat::Platform;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant};
+
+use blocks::Bar;
+use cli::{Args, Command};
+use config::Config;
+use error::{Error, Result};
+use event::Event;
+use output::{Format, Output};
 
 fn main() {
-    let mut system = sysinfo::System::new_all();
-    let mut sys_alt = systemstat::System::new();
-    system.refresh_all();
-
-    io::stdout().write_all(b"{ \"version\": 1 }[").unwrap();
-    let handle = thread::spawn(move || loop {
-        let mut status_lines: Vec<StatusLine> = vec![];
-        status_lines.push(os_info(&system));
-        status_lines.push(cpu_usage(&mut system));
-        status_lines.push(memory_usage(&mut system));
-        status_lines.append(&mut storage_info(&mut system));
-        status_lines.push(network_usage(&mut system));
-        if let Some(battery) = battery(&mut sys_alt) {
-            status_lines.push(battery);
-        }
-        status_lines.push(time());
-        println!("{},", serde_json::to_string(&status_lines).unwrap());
-
-        let waiting_time = time::Duration::from_secs(2);
-        spin_sleep::sleep(waiting_time);
-        let so = 1;
-    });
-
-    handle.join().unwrap();
+    match run() {
+        Ok(()) => {}
+        // i3bar went away, there is no one left to show the bar to.
+        Err(err) if err.is_broken_pipe() => process::exit(0),
+        Err(err) => exit_with_error(err),
+    }
 }
 
-fn os_info(sys: &sysinfo::System) -> StatusLine {
-    match sys.get_long_os_version() {
-        Some(os) => StatusLine {
-            full_text: os,
-            color: Color::RED.to_string(),
-            min_width: None,
-            align: None,
-        },
-        None => StatusLine {
-            full_text: "error".to_string(),
-            color: Color::RED.to_string(),
-            min_width: None,
-            align: None,
-        },
+fn run() -> Result<()> {
+    let args = Args::parse(std::env::args().skip(1)).map_err(Error::Args)?;
+    match &args.command {
+        Command::Help => return help(),
+        Command::ListBlocks => return list_blocks(),
+        Command::Msg(message) => return control::send(message),
+        _ => {}
     }
-}
 
-fn cpu_usage(sys: &mut sysinfo::System) -> StatusLine {
-    sys.refresh_cpu();
-    sys.refresh_cpu();
-    let load = sys.get_global_processor_info().get_cpu_usage();
-    StatusLine {
-        full_text: format!(" : {:>5.1} %", load),
-        color: Color::GREEN.to_string(),
-        min_width: None,
-        align: None,
+    let mut config = Config::load(args.config.as_deref())?;
+    if let Some(level) = args.log_level {
+        config.log.level = level;
     }
-}
+    logging::init(&config.log)?;
 
-fn memory_usage(sys: &mut sysinfo::System) -> StatusLine {
-    sys.refresh_memory();
-    let usage = sys.get_used_memory();
-    let total = sys.get_total_memory();
-
-    StatusLine {
-        full_text: format!(
-            " : {:.1}G / {:.1}G",
-            usage as f64 / 1000000.0,
-            total as f64 / 1000000.0
-        ),
-        color: Color::YELLOW.to_string(),
-        min_width: None,
-        align: None,
+    match args.command {
+        Command::CheckConfig => check_config(&config),
+        Command::Once => once(&config, args.output),
+        Command::Preview => run_bar(&config, args.config, Format::Terminal),
+        _ => run_bar(&config, args.config, args.output),
     }
 }
 
-fn storage_info(sys: &mut sysinfo::System) -> Vec<StatusLine> {
-    sys.refresh_disks_list();
-    let disks = sys.get_disks();
-    let mut disk_infos = vec![];
-
-    for disk in disks {
-        if disk.get_name().to_str().unwrap() != "/dev/sda2" {
+/// Feeds the bar until it goes away.
+/// `config_path` is where the configuration is read again from on `reload`.
+fn run_bar(config: &Config, config_path: Option<PathBuf>, format: Format) -> Result<()> {
+    let mut bar = Bar::new(config)?;
+    let (tx, events) = mpsc::channel();
+    // Before the header announces the signals, as their default action is
+    // to terminate the process.
+    signals::spawn_signal_listener(tx.clone())?;
+    let mut output = Output::new(format, io::stdout());
+    output.start()?;
+
+    if format.is_interactive() {
+        protocol::spawn_click_reader(tx.clone());
+    }
+    // The bar is still useful without the socket, e.g. when another bar
+    // already listens on it.
+    match control::spawn_listener(tx.clone()) {
+        Ok(path) => log::info!("listening for commands on {}", path.display()),
+        Err(err) => log::warn!("cannot listen for commands: {}", err),
+    }
+    bar.start(&tx);
+
+    let mut state = State {
+        paused: false,
+        config_path,
+        tx,
+    };
+    loop {
+        if state.paused {
+            wait_for_event(&events, None, &mut bar, &mut state);
             continue;
         }
-        disk_infos.push(StatusLine {
-            full_text: format!(
-                " : {:.1}G / {:.1}G",
-                (disk.get_total_space() - disk.get_available_space()) as f64 / 1000000000.0,
-                disk.get_total_space() as f64 / 1000000000.0
-            ),
-            color: Color::BLUE.to_string(),
-            min_width: None,
-            align: None,
-        });
+
+        if bar.update(Instant::now()) {
+            output.write(&bar.render())?;
+        }
+
+        let deadline = bar.next_deadline();
+        wait_for_event(&events, deadline, &mut bar, &mut state);
     }
-    disk_infos
 }
 
-fn network_usage(sys: &mut sysinfo::System) -> StatusLine {
-    sys.refresh_networks();
-    let networks = sys.get_networks();
-    let mut rx = 0;
-    let mut tx = 0;
-    for (_, data) in networks {
-        rx = rx + data.get_received();
-        tx = tx + data.get_transmitted();
-    }
+/// Blocks measuring rates or usage need two samples, taken this far apart.
+const ONCE_SAMPLING: Duration = Duration::from_millis(500);
 
-    StatusLine {
-        full_text: format!(
-            " : {:.1}M |  : {:.1}M",
-            (rx as f64) / 1000000.,
-            (tx as f64) / 1000000.
-        ),
-        color: Color::MAGENTA.to_string(),
-        min_width: None,
-        align: None,
-    }
+fn once(config: &Config, format: Format) -> Result<()> {
+    let mut bar = Bar::new(config)?;
+    bar.update(Instant::now());
+    spin_sleep::sleep(ONCE_SAMPLING);
+    bar.wake_all();
+    bar.update(Instant::now());
+    Output::new(format, io::stdout()).once(&bar.render())
 }
 
-fn time() -> StatusLine {
-    let now = chrono::Local::now();
-
-    StatusLine {
-        full_text: format!(
-            "{:02}/{:02}/{} {:02}:{:02} {} ",
-            now.day(),
-            now.month(),
-            now.year(),
-            now.hour12().1,
-            now.minute(),
-            match now.hour12().0 {
-                true => "PM",
-                false => "AM",
-            },
-        ),
-        color: Color::CYAN.to_string(),
-        min_width: None,
-        align: Some(Align::Right),
+/// Validates the configuration, blocks included, then prints it back with
+/// the defaults filled in, those of the blocks too.
+fn check_config(config: &Config) -> Result<()> {
+    Bar::new(config)?;
+    let mut resolved = config.clone();
+    for block in &mut resolved.blocks {
+        block.options = blocks::resolve_options(block)
+            .map_err(|err| Error::Data(format!("cannot print the configuration: {}", err)))?;
     }
+    let resolved = toml::Value::try_from(&resolved)
+        .and_then(|config| toml::to_string(&config))
+        .map_err(|err| Error::Data(format!("cannot print the configuration: {}", err)))?;
+    write!(io::stdout(), "{}", resolved)?;
+    Ok(())
 }
 
-fn battery(sys: &mut systemstat::System) -> Option<StatusLine> {
-    match sys.battery_life() {
-        Ok(battery) => {
-            let on_ac = match sys.on_ac_power() {
-                Ok(v) => v,
-                Err(_) => false,
-            };
-            return Some(StatusLine {
-                full_text: format!(
-                    "{} :  {:>5.1} %",
-                    battery_icon(battery.remaining_capacity, on_ac),
-                    battery.remaining_capacity * 100.0
-                ),
-                color: Color::GREEN.to_string(),
-                min_width: None,
-                align: None,
-            });
-        }
-        Err(_) => None,
+fn help() -> Result<()> {
+    let mut stdout = io::stdout();
+    writeln!(stdout, "{}", cli::USAGE)?;
+    for line in control::COMMANDS.lines() {
+        writeln!(stdout, "  {}", line)?;
     }
+    Ok(())
 }
 
-fn battery_icon(capacity: f32, on_ac: bool) -> char {
-    if on_ac {
-        return '';
-    } else {
-        let step = (capacity * 10.0) as i8;
-        match step {
-            9 => '',
-            8 => '',
-            7 => '',
-            6 => '',
-            5 => '',
-            4 => '',
-            3 => '',
-            2 => '',
-            1 => '',
-            0 => '',
-            _ => '',
-        }
+fn list_blocks() -> Result<()> {
+    let mut stdout = io::stdout();
+    for name in blocks::BLOCK_NAMES {
+        let placeholders = blocks::placeholders(name).unwrap_or_default();
+        writeln!(stdout, "{:<12} {}", name, placeholders.join(", "))?;
     }
+    Ok(())
+}
+
+fn exit_with_error<E: std::fmt::Display>(err: E) -> ! {
+    eprintln!("i3bar-rusty-ricer: {}", err);
+    process::exit(1);
 }
 
-#[derive(Serialize, Deserialize, Debug, Default)]
-struct StatusLine {
-    full_text: String,
-    color: String,
-    min_width: Option<u16>,
-    align: Option<Align>,
+/// State of the main loop beside the bar itself.
+struct State {
+    /// Set by signals while the bar is hidden.
+    paused: bool,
+    config_path: Option<PathBuf>,
+    /// Handed to the blocks of a reloaded bar.
+    tx: Sender<Event>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-enum Align {
-    #[serde(rename = "left")]
-    Left,
-    #[serde(rename = "right")]
-    Right,
-    #[serde(rename = "center")]
-    Center,
+/// Sleeps until `deadline` (forever if `None`), returning early to re-render
+/// when events come in. Events queued together are all handled before
+/// returning so they end up in a single status line.
+fn wait_for_event(
+    events: &Receiver<Event>,
+    deadline: Option<Instant>,
+    bar: &mut Bar,
+    state: &mut State,
+) {
+    let event = match deadline {
+        Some(deadline) => {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            match events.recv_timeout(timeout) {
+                Ok(event) => event,
+                Err(RecvTimeoutError::Timeout) => return,
+                Err(RecvTimeoutError::Disconnected) => return spin_sleep::sleep(timeout),
+            }
+        }
+        None => match events.recv() {
+            Ok(event) => event,
+            Err(_) => {
+                // Nothing is left to wake us up, so do not stay paused.
+                state.paused = false;
+                bar.set_paused(false);
+                return spin_sleep::sleep(Duration::from_secs(1));
+            }
+        },
+    };
+    handle_event(event, bar, state);
+    while let Ok(event) = events.try_recv() {
+        handle_event(event, bar, state);
+    }
 }
 
-struct Color;
+fn handle_event(event: Event, bar: &mut Bar, state: &mut State) {
+    match event {
+        Event::Click(click) => bar.click(&click),
+        Event::Update(index) => bar.wake(index),
+        Event::Stop => {
+            log::debug!("bar hidden, pausing updates");
+            state.paused = true;
+            bar.set_paused(true);
+        }
+        Event::Continue => {
+            log::debug!("bar shown, resuming updates");
+            state.paused = false;
+            bar.set_paused(false);
+        }
+        Event::Control(request) => {
+            let result = match request.command {
+                control::Command::Reload => reload(state, bar),
+                command => bar.control(&command),
+            };
+            // The client may have given up waiting.
+            let _ = request.reply.send(result);
+        }
+    }
+}
 
-impl Color {
-    const RED: &'static str = "#ea6962";
-    const GREEN: &'static str = "#a9b665";
-    const YELLOW: &'static str = "#d8a657";
-    const BLUE: &'static str = "#7daea3";
-    const MAGENTA: &'static str = "#d3869b";
-    const CYAN: &'static str = "#89b482";
+/// Replaces the bar with one built from the configuration read again, keeping
+/// the current one when the configuration is invalid. Logging is left as is.
+fn reload(state: &State, bar: &mut Bar) -> std::result::Result<(), String> {
+    let config = Config::load(state.config_path.as_deref()).map_err(|err| err.to_string())?;
+    let mut reloaded = Bar::new(&config).map_err(|err| err.to_string())?;
+    reloaded.set_paused(state.paused);
+    reloaded.start(&state.tx);
+    *bar = reloaded;
+    log::info!("configuration reloaded");
+    Ok(())
 }
@@ -0,0 +1,283 @@
+//! Ways of writing the status lines out: the i3bar protocol, which swaybar
+//! speaks as well, formatting tags for lemonbar and polybar, plain text for
+//! tmux, or colored text for a terminal.
+
+use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
+
+use crate::error::Result;
+use crate::protocol::{Header, StatusLine};
+
+/// Separator between blocks for outputs without one of their own.
+const SEPARATOR: &str = " | ";
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Format {
+    #[default]
+    I3bar,
+    /// `%{F#rrggbb}` tags, understood by lemonbar and polybar alike.
+    Lemonbar,
+    Plain,
+    Terminal,
+}
+
+pub const FORMAT_NAMES: &[&str] = &[
+    "i3bar", "swaybar", "lemonbar", "polybar", "plain", "terminal",
+];
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Format, String> {
+        match name {
+            "i3bar" | "swaybar" => Ok(Format::I3bar),
+            "lemonbar" | "polybar" => Ok(Format::Lemonbar),
+            "plain" => Ok(Format::Plain),
+            "terminal" => Ok(Format::Terminal),
+            _ => Err(format!(
+                "unknown output `{}`, expected one of {}",
+                name,
+                FORMAT_NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Format::I3bar => "i3bar",
+            Format::Lemonbar => "lemonbar",
+            Format::Plain => "plain",
+            Format::Terminal => "terminal",
+        })
+    }
+}
+
+impl Format {
+    /// Whether the bar reading the output sends click events on stdin and
+    /// stop/continue signals.
+    pub fn is_interactive(self) -> bool {
+        self == Format::I3bar
+    }
+}
+
+/// Writes the successive states of the bar to `out`.
+pub struct Output<W: Write> {
+    format: Format,
+    out: W,
+}
+
+impl<W: Write> Output<W> {
+    pub fn new(format: Format, out: W) -> Self {
+        Output { format, out }
+    }
+
+    /// Writes what comes before the first status line.
+    pub fn start(&mut self) -> Result<()> {
+        if self.format == Format::I3bar {
+            let header = serde_json::to_string(&Header::default())?;
+            write!(self.out, "{}\n[", header)?;
+            self.out.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Writes the whole bar, made of `lines`.
+    pub fn write(&mut self, lines: &[StatusLine]) -> Result<()> {
+        match self.format {
+            Format::I3bar => writeln!(self.out, "{},", serde_json::to_string(lines)?)?,
+            Format::Lemonbar => writeln!(self.out, "{}", join(lines, lemonbar))?,
+            Format::Plain => writeln!(self.out, "{}", join(lines, |l| l.full_text.clone()))?,
+            // Redraws the same terminal line over and over.
+            Format::Terminal => write!(self.out, "\r\x1b[2K{}\x1b[0m", join(lines, terminal))?,
+        }
+        self.out.flush()?;
+        Ok(())
+    }
+
+    /// Writes the bar a single time, for scripts rather than a bar: i3bar
+    /// output is the JSON array of the blocks alone, without the header.
+    pub fn once(&mut self, lines: &[StatusLine]) -> Result<()> {
+        match self.format {
+            Format::I3bar => writeln!(self.out, "{}", serde_json::to_string(lines)?)?,
+            Format::Lemonbar | Format::Plain => self.write(lines)?,
+            Format::Terminal => {
+                self.write(lines)?;
+                writeln!(self.out)?;
+            }
+        }
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// Renders every line with `render`, separated as the lines ask.
+fn join(lines: &[StatusLine], render: impl Fn(&StatusLine) -> String) -> String {
+    let mut joined = String::new();
+    for (i, line) in lines.iter().enumerate() {
+        if i > 0 {
+            let previous = &lines[i - 1];
+            joined.push_str(match previous.separator {
+                Some(false) => " ",
+                _ => SEPARATOR,
+            });
+        }
+        joined.push_str(&render(line));
+    }
+    joined
+}
+
+fn lemonbar(line: &StatusLine) -> String {
+    let mut text = line.full_text.replace('%', "%%");
+    if let Some(color) = &line.color {
+        text = format!("%{{F{}}}{}%{{F-}}", color, text);
+    }
+    if let Some(background) = &line.background {
+        text = format!("%{{B{}}}{}%{{B-}}", background, text);
+    }
+    if line.urgent == Some(true) {
+        text = format!("%{{+u}}{}%{{-u}}", text);
+    }
+    text
+}
+
+fn terminal(line: &StatusLine) -> String {
+    let mut codes = vec![];
+    if let Some((r, g, b)) = line.color.as_deref().and_then(rgb) {
+        codes.push(format!("38;2;{};{};{}", r, g, b));
+    }
+    if let Some((r, g, b)) = line.background.as_deref().and_then(rgb) {
+        codes.push(format!("48;2;{};{};{}", r, g, b));
+    }
+    if line.urgent == Some(true) {
+        codes.push("1".to_string());
+    }
+    match codes.is_empty() {
+        true => line.full_text.clone(),
+        false => format!("\x1b[{}m{}\x1b[0m", codes.join(";"), line.full_text),
+    }
+}
+
+/// Red, green and blue of a `#rrggbb` or `#rrggbbaa` color.
+fn rgb(color: &str) -> Option<(u8, u8, u8)> {
+    let hex = color.strip_prefix('#')?;
+    if (hex.len() != 6 && hex.len() != 8) || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    Some((channel(0)?, channel(2)?, channel(4)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(text: &str) -> StatusLine {
+        StatusLine {
+            full_text: text.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn written(format: Format, lines: &[StatusLine]) -> String {
+        let mut output = Output::new(format, vec![]);
+        output.write(lines).unwrap();
+        String::from_utf8(output.out).unwrap()
+    }
+
+    #[test]
+    fn tags_lemonbar_lines() {
+        let mut cpu = line("cpu");
+        assert_eq!(lemonbar(&cpu), "cpu");
+        cpu.color = Some("#ea6962".to_string());
+        assert_eq!(lemonbar(&cpu), "%{F#ea6962}cpu%{F-}");
+        cpu.background = Some("#282828".to_string());
+        assert_eq!(lemonbar(&cpu), "%{B#282828}%{F#ea6962}cpu%{F-}%{B-}");
+        cpu.urgent = Some(true);
+        assert_eq!(
+            lemonbar(&cpu),
+            "%{+u}%{B#282828}%{F#ea6962}cpu%{F-}%{B-}%{-u}"
+        );
+    }
+
+    #[test]
+    fn escapes_percent_signs_for_lemonbar() {
+        assert_eq!(lemonbar(&line("cpu: 12%")), "cpu: 12%%");
+        assert_eq!(lemonbar(&line("%{F#ff0000}")), "%%{F#ff0000}");
+    }
+
+    #[test]
+    fn separates_lines_as_they_ask() {
+        let mut lines = vec![line("a"), line("b"), line("c")];
+        assert_eq!(written(Format::Plain, &lines), "a | b | c\n");
+        // The separator after a line, not before it.
+        lines[0].separator = Some(false);
+        lines[1].separator = Some(true);
+        assert_eq!(written(Format::Plain, &lines), "a b | c\n");
+        lines[2].separator = Some(false);
+        assert_eq!(written(Format::Lemonbar, &lines), "a b | c\n");
+        assert_eq!(written(Format::Plain, &[]), "\n");
+    }
+
+    #[test]
+    fn colors_terminal_lines() {
+        let mut cpu = line("cpu");
+        assert_eq!(terminal(&cpu), "cpu");
+        cpu.color = Some("#ff8000".to_string());
+        cpu.background = Some("#00000080".to_string());
+        cpu.urgent = Some(true);
+        assert_eq!(
+            terminal(&cpu),
+            "\x1b[38;2;255;128;0;48;2;0;0;0;1mcpu\x1b[0m"
+        );
+        // Colors the terminal cannot show are left out.
+        cpu.color = Some("red".to_string());
+        cpu.urgent = None;
+        assert_eq!(terminal(&cpu), "\x1b[48;2;0;0;0mcpu\x1b[0m");
+    }
+
+    #[test]
+    fn parses_hex_colors() {
+        assert_eq!(rgb("#ea6962"), Some((0xea, 0x69, 0x62)));
+        assert_eq!(rgb("#EA6962"), Some((0xea, 0x69, 0x62)));
+        // The alpha channel is ignored.
+        assert_eq!(rgb("#ea696280"), Some((0xea, 0x69, 0x62)));
+    }
+
+    #[test]
+    fn rejects_invalid_colors() {
+        for color in [
+            "",
+            "#",
+            "ea6962",
+            "#ea696",
+            "#ea69620",
+            "#ea6962801",
+            "#fff",
+            "#gg0000",
+            "#+f0000",
+            "#ea69\u{e9}",
+            "red",
+        ] {
+            assert_eq!(rgb(color), None, "{}", color);
+        }
+    }
+
+    #[test]
+    fn writes_the_i3bar_header_then_an_infinite_array() {
+        let mut output = Output::new(Format::I3bar, vec![]);
+        output.start().unwrap();
+        output.write(&[line("a")]).unwrap();
+        output.write(&[line("b")]).unwrap();
+        let written = String::from_utf8(output.out).unwrap();
+        let (header, array) = written.split_once('\n').unwrap();
+        let header: serde_json::Value = serde_json::from_str(header).unwrap();
+        assert_eq!(header["version"], 1);
+        assert_eq!(
+            array,
+            "[[{\"full_text\":\"a\"}],\n[{\"full_text\":\"b\"}],\n"
+        );
+    }
+}
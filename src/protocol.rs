@@ -0,0 +1,187 @@
+use std::io::{self, BufRead};
+use std::sync::mpsc::Sender;
+use std::thread;
+
+use serde::{Deserialize, Serialize};
+
+use crate::event::Event;
+use crate::signals::{CONT_SIGNAL, STOP_SIGNAL};
+use crate::threshold::State;
+
+/// First object sent to i3bar, announcing which protocol features we use.
+#[derive(Serialize, Debug)]
+pub struct Header {
+    pub version: u8,
+    pub click_events: bool,
+    pub stop_signal: i32,
+    pub cont_signal: i32,
+}
+
+impl Default for Header {
+    fn default() -> Self {
+        Header {
+            version: 1,
+            click_events: true,
+            stop_signal: STOP_SIGNAL,
+            cont_signal: CONT_SIGNAL,
+        }
+    }
+}
+
+/// One block of the status line, as described by the i3bar protocol.
+/// Unset fields are left out so i3bar falls back to its own defaults.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct StatusLine {
+    pub full_text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub short_text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub background: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub border: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub border_top: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub border_right: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub border_bottom: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub border_left: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_width: Option<MinWidth>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub align: Option<Align>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub urgent: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub separator: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub separator_block_width: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub markup: Option<Markup>,
+    /// Not part of the protocol, picks the theme colors of the block.
+    #[serde(skip)]
+    pub state: State,
+}
+
+/// Either a width in pixels, or a text whose rendered width is used instead.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum MinWidth {
+    Pixels(u16),
+    Text(String),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum Align {
+    #[serde(rename = "left")]
+    Left,
+    #[serde(rename = "right")]
+    Right,
+    #[serde(rename = "center")]
+    Center,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum Markup {
+    #[serde(rename = "pango")]
+    Pango,
+    #[serde(rename = "none")]
+    None,
+}
+
+/// A click reported by i3bar on our stdin.
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct ClickEvent {
+    pub name: Option<String>,
+    pub instance: Option<String>,
+    pub button: u8,
+    pub modifiers: Vec<String>,
+    pub x: i32,
+    pub y: i32,
+    pub relative_x: i32,
+    pub relative_y: i32,
+    pub output_x: i32,
+    pub output_y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+/// Spawns a thread reading the infinite JSON array of click events i3bar
+/// writes on stdin. The thread exits once stdin is closed.
+pub fn spawn_click_reader(tx: Sender<Event>) {
+    thread::spawn(move || {
+        let stdin = io::stdin();
+        for line in stdin.lock().lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => break,
+            };
+            if let Some(event) = parse_click_event(&line) {
+                if tx.send(Event::Click(event)).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+}
+
+/// i3bar sends `[` on its own line, then one event per line, every event
+/// after the first being prefixed with a comma.
+fn parse_click_event(line: &str) -> Option<ClickEvent> {
+    let line = line.trim().trim_start_matches(['[', ',']);
+    if line.is_empty() {
+        return None;
+    }
+    serde_json::from_str(line).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EVENT: &str =
+        r#"{"name":"disk","instance":"1:/home","button":3,"modifiers":["Shift"],"x":1200,"y":10}"#;
+
+    #[test]
+    fn skips_the_opening_bracket() {
+        assert!(parse_click_event("[").is_none());
+        assert!(parse_click_event(" [ \n").is_none());
+        assert!(parse_click_event("").is_none());
+    }
+
+    #[test]
+    fn parses_the_first_event() {
+        let event = parse_click_event(EVENT).unwrap();
+        assert_eq!(event.name.as_deref(), Some("disk"));
+        assert_eq!(event.instance.as_deref(), Some("1:/home"));
+        assert_eq!(event.button, 3);
+        assert_eq!(event.modifiers, ["Shift"]);
+        assert_eq!((event.x, event.y), (1200, 10));
+        assert_eq!(event.width, 0);
+    }
+
+    #[test]
+    fn parses_events_prefixed_with_a_comma() {
+        let event = parse_click_event(&format!(",{}\n", EVENT)).unwrap();
+        assert_eq!(event.instance.as_deref(), Some("1:/home"));
+        // Some versions of i3bar send the first event on the bracket's line.
+        let event = parse_click_event(&format!("[{}", EVENT)).unwrap();
+        assert_eq!(event.button, 3);
+    }
+
+    #[test]
+    fn ignores_invalid_events() {
+        assert!(parse_click_event(",").is_none());
+        assert!(parse_click_event(r#",{"name":"disk","#).is_none());
+        assert!(parse_click_event(r#"{"button":"left"}"#).is_none());
+        assert!(parse_click_event("]").is_none());
+    }
+}
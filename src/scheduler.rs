@@ -0,0 +1,301 @@
+use std::fmt;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use serde::{Serialize, Serializer};
+
+/// How often a block is updated.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interval {
+    Every(Duration),
+    /// Updated every period, right after the wall clock crosses a multiple
+    /// of it, e.g. on every minute for a clock without seconds.
+    Aligned(Duration),
+    /// Updated a single time, at startup.
+    Once,
+}
+
+impl Interval {
+    pub fn from_secs(secs: u64) -> Self {
+        Interval::Every(Duration::from_secs(secs))
+    }
+}
+
+/// Shortest interval accepted, anything shorter only burns CPU.
+const MIN_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Accepts a number of seconds, at least `MIN_INTERVAL`, or the string
+/// `"once"`.
+impl<'de> Deserialize<'de> for Interval {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct IntervalVisitor;
+
+        impl<'de> Visitor<'de> for IntervalVisitor {
+            type Value = Interval;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a number of seconds of at least 0.1 or \"once\"")
+            }
+
+            fn visit_f64<E: de::Error>(self, secs: f64) -> Result<Interval, E> {
+                match Duration::try_from_secs_f64(secs) {
+                    Ok(every) if every >= MIN_INTERVAL => Ok(Interval::Every(every)),
+                    _ => Err(E::invalid_value(de::Unexpected::Float(secs), &self)),
+                }
+            }
+
+            fn visit_i64<E: de::Error>(self, secs: i64) -> Result<Interval, E> {
+                if secs > 0 {
+                    Ok(Interval::from_secs(secs as u64))
+                } else {
+                    Err(E::invalid_value(de::Unexpected::Signed(secs), &self))
+                }
+            }
+
+            fn visit_u64<E: de::Error>(self, secs: u64) -> Result<Interval, E> {
+                self.visit_i64(secs as i64)
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Interval, E> {
+                match value {
+                    "once" => Ok(Interval::Once),
+                    _ => Err(E::invalid_value(de::Unexpected::Str(value), &self)),
+                }
+            }
+        }
+
+        deserializer.deserialize_any(IntervalVisitor)
+    }
+}
+
+const BOUNDARY_MARGIN: Duration = Duration::from_millis(1);
+
+/// How often the wall clock is checked while an aligned block waits, as it
+/// can jump ahead of `Instant`, e.g. after a suspend.
+const WALL_CLOCK_CHECK: Duration = Duration::from_secs(1);
+
+/// Time left until the wall clock reaches the next multiple of `every`.
+fn until_boundary(every: Duration) -> Duration {
+    let since_epoch = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let every = every.as_nanos().max(1);
+    // Waking up a hair late makes sure the boundary has been crossed.
+    Duration::from_nanos((every - since_epoch.as_nanos() % every) as u64) + BOUNDARY_MARGIN
+}
+
+/// Written back as it is read: seconds or `"once"`.
+impl Serialize for Interval {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Interval::Every(every) | Interval::Aligned(every) => match every.subsec_nanos() {
+                0 => serializer.serialize_u64(every.as_secs()),
+                _ => serializer.serialize_f64(every.as_secs_f64()),
+            },
+            Interval::Once => serializer.serialize_str("once"),
+        }
+    }
+}
+
+/// Keeps track of when each block is due for an update.
+pub struct Scheduler {
+    /// Next update of each block, `None` once a block will never be updated
+    /// again.
+    deadlines: Vec<Option<Instant>>,
+    /// The same deadlines on the wall clock, for aligned blocks only.
+    wall_deadlines: Vec<Option<SystemTime>>,
+}
+
+impl Scheduler {
+    /// Every one of the `count` blocks starts due right away.
+    pub fn new(count: usize, now: Instant) -> Self {
+        Scheduler {
+            deadlines: vec![Some(now); count],
+            wall_deadlines: vec![None; count],
+        }
+    }
+
+    pub fn is_due(&self, index: usize, now: Instant) -> bool {
+        match (self.deadlines[index], self.wall_deadlines[index]) {
+            (Some(deadline), _) if deadline <= now => true,
+            (Some(_), Some(wall_deadline)) => wall_deadline <= SystemTime::now(),
+            _ => false,
+        }
+    }
+
+    /// Plans the update following the one the block at `index` just went
+    /// through. Deadlines move by whole intervals so they do not drift, unless
+    /// we fell behind by more than an interval. An interval too long to be
+    /// represented means no update at all.
+    pub fn reschedule(&mut self, index: usize, now: Instant, interval: Interval) {
+        self.wall_deadlines[index] = None;
+        self.deadlines[index] = match (self.deadlines[index], interval) {
+            (_, Interval::Once) => None,
+            (Some(deadline), Interval::Every(every))
+                if deadline.checked_add(every).is_none_or(|next| next > now) =>
+            {
+                deadline.checked_add(every)
+            }
+            (_, Interval::Every(every)) => now.checked_add(every),
+            // Measured from the actual time rather than `now`, which may be a
+            // bit behind when other blocks were updated first.
+            (_, Interval::Aligned(every)) => {
+                let until = until_boundary(every);
+                self.wall_deadlines[index] = SystemTime::now().checked_add(until);
+                Instant::now().checked_add(until)
+            }
+        };
+    }
+
+    /// Makes the block at `index` due after `delay`, e.g. to retry a failed
+    /// update.
+    pub fn delay(&mut self, index: usize, now: Instant, delay: Duration) {
+        self.deadlines[index] = Some(now + delay);
+        self.wall_deadlines[index] = None;
+    }
+
+    /// Makes the block at `index` due right away.
+    pub fn wake(&mut self, index: usize, now: Instant) {
+        self.deadlines[index] = Some(now);
+        self.wall_deadlines[index] = None;
+    }
+
+    /// The earliest deadline, `None` when no block has to be updated again.
+    /// Aligned blocks are checked against the wall clock every second.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        let check = Instant::now() + WALL_CLOCK_CHECK;
+        self.deadlines
+            .iter()
+            .zip(&self.wall_deadlines)
+            .filter_map(|(deadline, wall_deadline)| match wall_deadline {
+                Some(_) => deadline.map(|deadline| deadline.min(check)),
+                None => *deadline,
+            })
+            .min()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    struct Config {
+        interval: Interval,
+    }
+
+    fn parse(interval: &str) -> Result<Interval, toml::de::Error> {
+        toml::from_str::<Config>(&format!("interval = {}", interval)).map(|c| c.interval)
+    }
+
+    #[test]
+    fn parses_intervals() {
+        assert_eq!(parse("2").unwrap(), Interval::from_secs(2));
+        assert_eq!(
+            parse("0.5").unwrap(),
+            Interval::Every(Duration::from_millis(500))
+        );
+        assert_eq!(parse("\"once\"").unwrap(), Interval::Once);
+    }
+
+    #[test]
+    fn rejects_invalid_intervals() {
+        for interval in [
+            "0",
+            "0.0",
+            "-1",
+            "-1.5",
+            "0.05",
+            "1e300",
+            "\"never\"",
+            "nan",
+        ] {
+            assert!(parse(interval).is_err(), "{} accepted", interval);
+        }
+    }
+
+    #[test]
+    fn intervals_round_trip() {
+        for interval in ["2", "0.5", "\"once\""] {
+            let parsed = parse(interval).unwrap();
+            let written = toml::Value::try_from(parsed).unwrap().to_string();
+            assert_eq!(parse(&written).unwrap(), parsed);
+        }
+    }
+
+    #[test]
+    fn reschedules_without_drifting() {
+        let start = Instant::now();
+        let every = Duration::from_secs(1);
+        let mut scheduler = Scheduler::new(1, start);
+        assert!(scheduler.is_due(0, start));
+
+        // Updated a bit late, the next deadline still follows the previous.
+        scheduler.reschedule(0, start + Duration::from_millis(10), Interval::Every(every));
+        assert_eq!(scheduler.next_deadline(), Some(start + every));
+        assert!(!scheduler.is_due(0, start + Duration::from_millis(999)));
+        assert!(scheduler.is_due(0, start + every));
+    }
+
+    #[test]
+    fn reschedules_from_now_when_behind() {
+        let start = Instant::now();
+        let every = Duration::from_secs(1);
+        let mut scheduler = Scheduler::new(1, start);
+        let late = start + Duration::from_secs(5);
+        scheduler.reschedule(0, late, Interval::Every(every));
+        assert_eq!(scheduler.next_deadline(), Some(late + every));
+    }
+
+    #[test]
+    fn never_reschedules_once_or_overflowing_intervals() {
+        let start = Instant::now();
+        let mut scheduler = Scheduler::new(2, start);
+        scheduler.reschedule(0, start, Interval::Once);
+        scheduler.reschedule(1, start, Interval::Every(Duration::MAX));
+        assert_eq!(scheduler.next_deadline(), None);
+        assert!(!scheduler.is_due(0, start + Duration::from_secs(3600)));
+
+        scheduler.wake(1, start);
+        assert!(scheduler.is_due(1, start));
+    }
+
+    #[test]
+    fn delays_failed_updates() {
+        let start = Instant::now();
+        let mut scheduler = Scheduler::new(1, start);
+        scheduler.delay(0, start, Duration::from_secs(2));
+        assert_eq!(
+            scheduler.next_deadline(),
+            Some(start + Duration::from_secs(2))
+        );
+    }
+
+    #[test]
+    fn checks_the_wall_clock_of_aligned_blocks() {
+        let start = Instant::now();
+        let mut scheduler = Scheduler::new(1, start);
+        scheduler.reschedule(0, start, Interval::Aligned(Duration::from_secs(3600)));
+        let deadline = scheduler.next_deadline().unwrap();
+        assert!(deadline <= Instant::now() + WALL_CLOCK_CHECK);
+        assert!(!scheduler.is_due(0, Instant::now()));
+
+        // The wall clock jumped past the boundary, e.g. during a suspend,
+        // while `Instant` did not.
+        scheduler.wall_deadlines[0] = Some(SystemTime::now() - Duration::from_secs(1));
+        assert!(scheduler.is_due(0, Instant::now()));
+    }
+
+    #[test]
+    fn aligns_on_wall_clock_boundaries() {
+        let every = Duration::from_secs(60);
+        let until = until_boundary(every);
+        assert!(until > BOUNDARY_MARGIN && until <= every + BOUNDARY_MARGIN);
+        let boundary = SystemTime::now() + until - BOUNDARY_MARGIN;
+        let since_epoch = boundary.duration_since(UNIX_EPOCH).unwrap();
+        // Within the few microseconds elapsed since `until_boundary`.
+        assert!(since_epoch.as_nanos() % every.as_nanos() < 1_000_000);
+    }
+}
@@ -0,0 +1,29 @@
+use std::sync::mpsc::Sender;
+use std::thread;
+
+use signal_hook::consts::{SIGUSR1, SIGUSR2};
+use signal_hook::iterator::Signals;
+
+use crate::event::Event;
+
+/// Signal i3bar sends when the bar is hidden.
+pub const STOP_SIGNAL: i32 = SIGUSR1;
+/// Signal i3bar sends when the bar is shown again.
+pub const CONT_SIGNAL: i32 = SIGUSR2;
+
+/// Forwards the stop and continue signals to the main loop.
+pub fn spawn_signal_listener(tx: Sender<Event>) -> std::io::Result<()> {
+    let mut signals = Signals::new([STOP_SIGNAL, CONT_SIGNAL])?;
+    thread::spawn(move || {
+        for signal in signals.forever() {
+            let event = match signal {
+                STOP_SIGNAL => Event::Stop,
+                _ => Event::Continue,
+            };
+            if tx.send(event).is_err() {
+                break;
+            }
+        }
+    });
+    Ok(())
+}
@@ -0,0 +1,508 @@
+//! Color themes: a palette of named colors and the semantic roles blocks are
+//! styled with.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::protocol::StatusLine;
+use crate::threshold::State;
+
+pub const DEFAULT_THEME: &str = "gruvbox-material";
+
+/// Themes shipped with the binary, by name.
+pub const BUILTIN_THEMES: &[(&str, &str)] = &[
+    (
+        "gruvbox-material",
+        include_str!("themes/gruvbox-material.toml"),
+    ),
+    ("nord", include_str!("themes/nord.toml")),
+    ("plain", include_str!("themes/plain.toml")),
+    ("solarized-dark", include_str!("themes/solarized-dark.toml")),
+];
+
+/// A theme, as found in a theme file or in the `theme` table of a block.
+///
+/// Colors are either `#rrggbb` or the name of a palette entry.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    /// Theme this one only overrides parts of.
+    base: Option<String>,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    palette: HashMap<String, String>,
+    /// Text color of blocks without a color of their own.
+    foreground: Option<String>,
+    background: Option<String>,
+    separator: Option<bool>,
+    separator_block_width: Option<u16>,
+    /// Style of blocks in no particular state. Setting its color overrides
+    /// the palette color of each block.
+    #[serde(skip_serializing_if = "Style::is_empty")]
+    idle: Style,
+    #[serde(skip_serializing_if = "Style::is_empty")]
+    info: Style,
+    #[serde(skip_serializing_if = "Style::is_empty")]
+    good: Style,
+    #[serde(skip_serializing_if = "Style::is_empty")]
+    warning: Style,
+    #[serde(skip_serializing_if = "Style::is_empty")]
+    critical: Style,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Style {
+    color: Option<String>,
+    background: Option<String>,
+}
+
+impl Style {
+    fn is_empty(&self) -> bool {
+        self.color.is_none() && self.background.is_none()
+    }
+
+    fn merge(&self, overrides: &Style) -> Style {
+        Style {
+            color: overrides.color.clone().or_else(|| self.color.clone()),
+            background: overrides
+                .background
+                .clone()
+                .or_else(|| self.background.clone()),
+        }
+    }
+}
+
+impl Theme {
+    /// Loads a built-in theme, or a theme file. `name` is taken as a path if
+    /// it looks like one, otherwise it is looked up in the `themes`
+    /// directory next to the config file, which is in `config_dir`. Relative
+    /// paths are relative to `config_dir` as well.
+    pub fn load(name: &str, config_dir: Option<&Path>) -> Result<Theme, String> {
+        let theme = Theme::load_unchecked(name, config_dir, 0)?;
+        theme
+            .validate()
+            .map_err(|err| format!("theme `{}`: {}", name, err))?;
+        Ok(theme)
+    }
+
+    fn load_unchecked(
+        name: &str,
+        config_dir: Option<&Path>,
+        depth: usize,
+    ) -> Result<Theme, String> {
+        if depth > 8 {
+            return Err(format!("theme `{}`: too many nested `base` themes", name));
+        }
+        let theme: Theme = match BUILTIN_THEMES.iter().find(|(builtin, _)| *builtin == name) {
+            Some((_, content)) => toml::from_str(content).unwrap(),
+            None => {
+                let path = theme_path(name, config_dir)
+                    .ok_or_else(|| format!("unknown theme `{}`", name))?;
+                let content = match fs::read_to_string(&path) {
+                    Ok(content) => content,
+                    Err(err) if err.kind() == io::ErrorKind::NotFound && !is_path(name) => {
+                        return Err(format!(
+                            "unknown theme `{}`, expected one of {} or a file in {}",
+                            name,
+                            BUILTIN_THEMES
+                                .iter()
+                                .map(|(builtin, _)| *builtin)
+                                .collect::<Vec<_>>()
+                                .join(", "),
+                            path.parent().unwrap().display()
+                        ))
+                    }
+                    Err(err) => {
+                        return Err(format!("cannot read theme {}: {}", path.display(), err))
+                    }
+                };
+                toml::from_str(&content)
+                    .map_err(|err| format!("invalid theme {}: {}", path.display(), err))?
+            }
+        };
+        match &theme.base {
+            Some(base) => Ok(Theme::load_unchecked(base, config_dir, depth + 1)?.merge(&theme)),
+            None => Ok(theme),
+        }
+    }
+
+    /// Applies the overrides given in the `theme` table of a block.
+    pub fn with_overrides(
+        &self,
+        overrides: &Theme,
+        config_dir: Option<&Path>,
+    ) -> Result<Theme, String> {
+        let base = match &overrides.base {
+            Some(base) => Theme::load_unchecked(base, config_dir, 1)?,
+            None => self.clone(),
+        };
+        let theme = base.merge(overrides);
+        theme.validate()?;
+        Ok(theme)
+    }
+
+    fn merge(&self, overrides: &Theme) -> Theme {
+        let mut palette = self.palette.clone();
+        palette.extend(overrides.palette.clone());
+        Theme {
+            base: None,
+            palette,
+            foreground: overrides
+                .foreground
+                .clone()
+                .or_else(|| self.foreground.clone()),
+            background: overrides
+                .background
+                .clone()
+                .or_else(|| self.background.clone()),
+            separator: overrides.separator.or(self.separator),
+            separator_block_width: overrides
+                .separator_block_width
+                .or(self.separator_block_width),
+            idle: self.idle.merge(&overrides.idle),
+            info: self.info.merge(&overrides.info),
+            good: self.good.merge(&overrides.good),
+            warning: self.warning.merge(&overrides.warning),
+            critical: self.critical.merge(&overrides.critical),
+        }
+    }
+
+    /// Checks that every color of the theme can be resolved.
+    fn validate(&self) -> Result<(), String> {
+        for (name, color) in &self.palette {
+            if !color.starts_with('#') {
+                return Err(format!("palette color `{}` must start with `#`", name));
+            }
+        }
+        let styles = [
+            ("idle", &self.idle),
+            ("info", &self.info),
+            ("good", &self.good),
+            ("warning", &self.warning),
+            ("critical", &self.critical),
+        ];
+        let mut colors = vec![
+            ("foreground", &self.foreground),
+            ("background", &self.background),
+        ];
+        for (role, style) in &styles {
+            colors.push((role, &style.color));
+            colors.push((role, &style.background));
+        }
+        for (role, color) in colors {
+            if let Some(color) = color {
+                self.check_color(color)
+                    .map_err(|err| format!("in `{}`: {}", role, err))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn check_color(&self, color: &str) -> Result<(), String> {
+        match self.resolve(color) {
+            Some(_) => Ok(()),
+            None => Err(format!(
+                "unknown color `{}`, expected `#rrggbb` or one of the palette: {}",
+                color,
+                self.palette_names().join(", ")
+            )),
+        }
+    }
+
+    fn palette_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.palette.keys().map(|name| name.as_str()).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Turns a palette name into its color, `#rrggbb` colors are kept as is.
+    pub fn resolve(&self, color: &str) -> Option<String> {
+        match color.starts_with('#') {
+            true => Some(color.to_string()),
+            false => self.palette.get(color).cloned(),
+        }
+    }
+
+    fn style(&self, state: State) -> &Style {
+        match state {
+            State::Good => &self.good,
+            State::Idle => &self.idle,
+            State::Info => &self.info,
+            State::Warning => &self.warning,
+            State::Critical => &self.critical,
+        }
+    }
+
+    /// Fills the colors `line` leaves unset from the role matching its state.
+    /// Idle lines use `color` when given, then the idle role, then `accent`,
+    /// the palette color of the block.
+    pub fn apply(&self, line: &mut StatusLine, color: Option<&str>, accent: &str) {
+        let style = self.style(line.state);
+        let resolve = |color: &Option<String>| color.as_deref().and_then(|c| self.resolve(c));
+
+        if line.color.is_none() {
+            line.color = match line.state {
+                State::Idle => color
+                    .and_then(|c| self.resolve(c))
+                    .or_else(|| resolve(&style.color))
+                    .or_else(|| self.resolve(accent)),
+                _ => resolve(&style.color),
+            }
+            .or_else(|| resolve(&self.foreground));
+        }
+        if line.background.is_none() {
+            line.background = resolve(&style.background).or_else(|| resolve(&self.background));
+        }
+        if line.state == State::Critical {
+            line.urgent = Some(true);
+        }
+        if line.separator.is_none() {
+            line.separator = self.separator;
+        }
+        if line.separator_block_width.is_none() {
+            line.separator_block_width = self.separator_block_width;
+        }
+    }
+}
+
+/// Where the theme called `name` is looked for when it is not built in.
+fn theme_path(name: &str, config_dir: Option<&Path>) -> Option<PathBuf> {
+    if is_path(name) {
+        return Some(match config_dir {
+            Some(config_dir) => config_dir.join(name),
+            None => PathBuf::from(name),
+        });
+    }
+    Some(config_dir?.join("themes").join(format!("{}.toml", name)))
+}
+
+fn is_path(name: &str) -> bool {
+    name.contains('/') || name.ends_with(".toml")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::fake_sysfs::FakeSysfs;
+
+    fn builtin(name: &str) -> Theme {
+        Theme::load(name, None).unwrap()
+    }
+
+    fn overrides(content: &str) -> Theme {
+        toml::from_str(content).unwrap()
+    }
+
+    fn applied(theme: &Theme, state: State, color: Option<&str>) -> StatusLine {
+        let mut line = StatusLine {
+            state,
+            ..Default::default()
+        };
+        theme.apply(&mut line, color, "green");
+        line
+    }
+
+    #[test]
+    fn loads_every_builtin_theme() {
+        for (name, _) in BUILTIN_THEMES {
+            builtin(name);
+        }
+        assert!(BUILTIN_THEMES
+            .iter()
+            .any(|(name, _)| *name == DEFAULT_THEME));
+    }
+
+    #[test]
+    fn chains_base_themes() {
+        let config = FakeSysfs::new("theme-base");
+        config
+            .write(
+                "themes/frost.toml",
+                "base = \"nord\"\nbackground = \"#2e3440\"\n[palette]\nred = \"#ff0000\"",
+            )
+            .write(
+                "themes/frostier.toml",
+                "base = \"frost\"\n[warning]\ncolor = \"red\"",
+            );
+        let theme = Theme::load("frostier", Some(config.path())).unwrap();
+        assert_eq!(theme.base, None);
+        // From nord, through frost.
+        assert_eq!(theme.foreground.as_deref(), Some("#d8dee9"));
+        assert_eq!(theme.resolve("cyan").as_deref(), Some("#88c0d0"));
+        // From frost.
+        assert_eq!(theme.background.as_deref(), Some("#2e3440"));
+        assert_eq!(theme.resolve("red").as_deref(), Some("#ff0000"));
+        // Its own, still resolved through the palette of the others.
+        let line = applied(&theme, State::Warning, None);
+        assert_eq!(line.color.as_deref(), Some("#ff0000"));
+        // The critical role of nord is kept as a whole.
+        let line = applied(&theme, State::Critical, None);
+        assert_eq!(line.color.as_deref(), Some("#2e3440"));
+        assert_eq!(line.background.as_deref(), Some("#ff0000"));
+    }
+
+    #[test]
+    fn rejects_cycles_of_base_themes() {
+        let config = FakeSysfs::new("theme-cycle");
+        config
+            .write("themes/a.toml", "base = \"b\"")
+            .write("themes/b.toml", "base = \"a\"");
+        let err = Theme::load("a", Some(config.path())).unwrap_err();
+        assert!(err.contains("too many nested `base` themes"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_unknown_themes_and_colors() {
+        let err = Theme::load("gruvbox", None).unwrap_err();
+        assert_eq!(err, "unknown theme `gruvbox`");
+        let config = FakeSysfs::new("theme-unknown");
+        config.write("themes/pink.toml", "foreground = \"pink\"");
+        let err = Theme::load("pink", Some(config.path())).unwrap_err();
+        assert!(err.starts_with("theme `pink`: in `foreground`: unknown color `pink`"));
+        let err = Theme::load("nord", None)
+            .unwrap()
+            .with_overrides(&overrides("[good]\ncolor = \"lime\""), None)
+            .unwrap_err();
+        assert!(
+            err.starts_with("in `good`: unknown color `lime`"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn overrides_parts_of_the_theme_for_a_block() {
+        let theme = builtin("gruvbox-material");
+        let block_theme = theme
+            .with_overrides(
+                &overrides(
+                    "[palette]\npink = \"#ffc0cb\"\n[critical]\ncolor = \"#ffffff\"\nbackground = \"pink\"",
+                ),
+                None,
+            )
+            .unwrap();
+        let line = applied(&block_theme, State::Critical, None);
+        assert_eq!(line.color.as_deref(), Some("#ffffff"));
+        assert_eq!(line.background.as_deref(), Some("#ffc0cb"));
+        assert_eq!(line.urgent, Some(true));
+        // The rest of the theme is left as is, the bar's theme too.
+        let line = applied(&block_theme, State::Warning, None);
+        assert_eq!(line.color.as_deref(), Some("#d8a657"));
+        assert_eq!(theme.resolve("pink"), None);
+
+        let block_theme = theme
+            .with_overrides(&overrides("base = \"nord\""), None)
+            .unwrap();
+        assert_eq!(block_theme.foreground.as_deref(), Some("#d8dee9"));
+    }
+
+    #[test]
+    fn resolves_palette_names() {
+        let theme = builtin("solarized-dark");
+        assert_eq!(theme.resolve("blue").as_deref(), Some("#268bd2"));
+        assert_eq!(theme.resolve("#123456").as_deref(), Some("#123456"));
+        assert_eq!(theme.resolve("teal"), None);
+        assert!(theme.check_color("magenta").is_ok());
+        assert_eq!(
+            theme.check_color("teal").unwrap_err(),
+            "unknown color `teal`, expected `#rrggbb` or one of the palette: \
+             blue, cyan, green, magenta, red, yellow"
+        );
+    }
+
+    #[test]
+    fn picks_the_color_of_idle_lines_in_order() {
+        let theme = builtin("nord");
+        // The palette color of the block, then the color given to the block.
+        assert_eq!(
+            applied(&theme, State::Idle, None).color.as_deref(),
+            Some("#a3be8c")
+        );
+        assert_eq!(
+            applied(&theme, State::Idle, Some("cyan")).color.as_deref(),
+            Some("#88c0d0")
+        );
+
+        // The idle role beats the palette color of the block, not the color
+        // given to the block.
+        let theme = theme
+            .with_overrides(&overrides("[idle]\ncolor = \"#eceff4\""), None)
+            .unwrap();
+        assert_eq!(
+            applied(&theme, State::Idle, None).color.as_deref(),
+            Some("#eceff4")
+        );
+        assert_eq!(
+            applied(&theme, State::Idle, Some("cyan")).color.as_deref(),
+            Some("#88c0d0")
+        );
+
+        // The foreground when the palette color of the block is missing.
+        let mut palette_less = builtin("nord");
+        palette_less.palette.clear();
+        assert_eq!(
+            applied(&palette_less, State::Idle, None).color.as_deref(),
+            Some("#d8dee9")
+        );
+    }
+
+    #[test]
+    fn keeps_the_colors_a_line_sets() {
+        let theme = builtin("nord");
+        let mut line = StatusLine {
+            color: Some("#000000".to_string()),
+            separator: Some(false),
+            state: State::Warning,
+            ..Default::default()
+        };
+        theme.apply(&mut line, Some("red"), "green");
+        assert_eq!(line.color.as_deref(), Some("#000000"));
+        assert_eq!(line.separator, Some(false));
+        assert_eq!(line.urgent, None);
+    }
+
+    #[test]
+    fn leaves_colors_to_i3bar_with_the_plain_theme() {
+        let theme = builtin("plain");
+        let line = applied(&theme, State::Critical, None);
+        assert_eq!(line.color, None);
+        assert_eq!(line.background, None);
+        assert_eq!(line.urgent, Some(true));
+        assert_eq!(
+            applied(&theme, State::Idle, Some("#ff0000"))
+                .color
+                .as_deref(),
+            Some("#ff0000")
+        );
+    }
+
+    #[test]
+    fn looks_for_theme_files_next_to_the_config() {
+        let dir = Path::new("/etc/bar");
+        assert_eq!(
+            theme_path("mine", Some(dir)),
+            Some(PathBuf::from("/etc/bar/themes/mine.toml"))
+        );
+        assert_eq!(theme_path("mine", None), None);
+        assert_eq!(
+            theme_path("mine.toml", Some(dir)),
+            Some(PathBuf::from("/etc/bar/mine.toml"))
+        );
+        assert_eq!(
+            theme_path("colors/mine", Some(dir)),
+            Some(PathBuf::from("/etc/bar/colors/mine"))
+        );
+        assert_eq!(
+            theme_path("/usr/share/mine.toml", Some(dir)),
+            Some(PathBuf::from("/usr/share/mine.toml"))
+        );
+        assert_eq!(
+            theme_path("colors/mine.toml", None),
+            Some(PathBuf::from("colors/mine.toml"))
+        );
+    }
+}
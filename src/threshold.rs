@@ -0,0 +1,203 @@
+//! Thresholds turning a block's value into a state (good, warning,
+//! critical) which picks the colors of the block from the theme.
+
+use serde::{Deserialize, Serialize};
+
+/// States ordered from the best to the worst.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum State {
+    Good,
+    /// No threshold reached, the block keeps its own color.
+    #[default]
+    Idle,
+    /// Worth noticing without being a problem, never set by thresholds.
+    Info,
+    Warning,
+    Critical,
+}
+
+/// Which end of the values is the bad one, set by the block type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// E.g. the usage of a disk.
+    HigherIsWorse,
+    /// E.g. the charge of a battery.
+    LowerIsWorse,
+}
+
+/// The `thresholds` table of a block. When given, it replaces the default
+/// thresholds of the block as a whole.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Thresholds {
+    /// Values beyond which the state is reached, in the direction of the
+    /// block type.
+    pub good: Option<f64>,
+    pub warning: Option<f64>,
+    pub critical: Option<f64>,
+    /// How far back past a threshold a value has to go to leave a state, so
+    /// a value hovering around a threshold does not flicker between states.
+    pub hysteresis: f64,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Thresholds {
+            good: None,
+            warning: None,
+            critical: None,
+            hysteresis: 1.0,
+        }
+    }
+}
+
+impl Thresholds {
+    pub fn new(good: Option<f64>, warning: f64, critical: f64) -> Self {
+        Thresholds {
+            good,
+            warning: Some(warning),
+            critical: Some(critical),
+            ..Default::default()
+        }
+    }
+
+    fn classify(&self, value: f64, direction: Direction) -> State {
+        let lower_is_worse = direction == Direction::LowerIsWorse;
+        let reached = |threshold: Option<f64>, worse: bool| match threshold {
+            Some(threshold) if worse == lower_is_worse => value <= threshold,
+            Some(threshold) => value >= threshold,
+            None => false,
+        };
+        if reached(self.critical, true) {
+            State::Critical
+        } else if reached(self.warning, true) {
+            State::Warning
+        } else if reached(self.good, false) {
+            State::Good
+        } else {
+            State::Idle
+        }
+    }
+
+    /// The state of `value` given the `previous` one: worse states are
+    /// entered as soon as their threshold is crossed, better ones only once
+    /// the value is `hysteresis` past the threshold.
+    pub fn state(&self, value: f64, previous: State, direction: Direction) -> State {
+        let state = self.classify(value, direction);
+        if state >= previous {
+            return state;
+        }
+        let worse_value = match direction {
+            Direction::LowerIsWorse => value - self.hysteresis,
+            Direction::HigherIsWorse => value + self.hysteresis,
+        };
+        self.classify(worse_value, direction).min(previous)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use Direction::{HigherIsWorse, LowerIsWorse};
+
+    #[test]
+    fn classifies_higher_values_as_worse() {
+        let thresholds = Thresholds::new(Some(20.0), 80.0, 95.0);
+        let state = |value| thresholds.state(value, State::Idle, HigherIsWorse);
+        assert_eq!(state(10.0), State::Good);
+        assert_eq!(state(19.0), State::Good);
+        assert_eq!(state(50.0), State::Idle);
+        assert_eq!(state(80.0), State::Warning);
+        assert_eq!(state(99.0), State::Critical);
+    }
+
+    #[test]
+    fn classifies_lower_values_as_worse() {
+        let thresholds = Thresholds::new(Some(80.0), 30.0, 10.0);
+        let state = |value| thresholds.state(value, State::Idle, LowerIsWorse);
+        assert_eq!(state(90.0), State::Good);
+        assert_eq!(state(50.0), State::Idle);
+        assert_eq!(state(30.0), State::Warning);
+        assert_eq!(state(5.0), State::Critical);
+    }
+
+    #[test]
+    fn single_threshold_follows_the_direction() {
+        let thresholds = Thresholds {
+            critical: Some(10.0),
+            ..Default::default()
+        };
+        assert_eq!(
+            thresholds.state(90.0, State::Idle, LowerIsWorse),
+            State::Idle
+        );
+        assert_eq!(
+            thresholds.state(5.0, State::Idle, LowerIsWorse),
+            State::Critical
+        );
+        assert_eq!(
+            thresholds.state(5.0, State::Idle, HigherIsWorse),
+            State::Idle
+        );
+        assert_eq!(
+            thresholds.state(90.0, State::Idle, HigherIsWorse),
+            State::Critical
+        );
+    }
+
+    #[test]
+    fn worse_states_are_entered_right_away() {
+        let thresholds = Thresholds::new(None, 80.0, 95.0);
+        assert_eq!(
+            thresholds.state(80.0, State::Idle, HigherIsWorse),
+            State::Warning
+        );
+        assert_eq!(
+            thresholds.state(95.0, State::Warning, HigherIsWorse),
+            State::Critical
+        );
+    }
+
+    #[test]
+    fn better_states_wait_for_the_hysteresis() {
+        let thresholds = Thresholds {
+            hysteresis: 2.0,
+            ..Thresholds::new(None, 80.0, 95.0)
+        };
+        let state = |value| thresholds.state(value, State::Critical, HigherIsWorse);
+        assert_eq!(state(94.0), State::Critical);
+        assert_eq!(state(93.0), State::Critical);
+        assert_eq!(state(92.5), State::Warning);
+        assert_eq!(state(78.0), State::Warning);
+        assert_eq!(state(77.5), State::Idle);
+
+        let thresholds = Thresholds {
+            hysteresis: 2.0,
+            ..Thresholds::new(Some(80.0), 30.0, 10.0)
+        };
+        let state = |value, previous| thresholds.state(value, previous, LowerIsWorse);
+        assert_eq!(state(12.0, State::Critical), State::Critical);
+        assert_eq!(state(12.5, State::Critical), State::Warning);
+        assert_eq!(state(81.0, State::Idle), State::Idle);
+        assert_eq!(state(82.0, State::Idle), State::Good);
+        // Leaving the good state is getting worse, so immediate.
+        assert_eq!(state(79.0, State::Good), State::Idle);
+    }
+
+    #[test]
+    fn hysteresis_never_makes_the_state_worse() {
+        let thresholds = Thresholds {
+            hysteresis: 10.0,
+            ..Thresholds::new(None, 80.0, 95.0)
+        };
+        assert_eq!(
+            thresholds.state(75.0, State::Idle, HigherIsWorse),
+            State::Idle
+        );
+        assert_eq!(
+            thresholds.state(90.0, State::Warning, HigherIsWorse),
+            State::Warning
+        );
+    }
+}
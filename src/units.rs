@@ -0,0 +1,86 @@
+//! Byte sizes scaled to SI (powers of 1000) or binary (powers of 1024)
+//! prefixes.
+
+use serde::{Deserialize, Serialize};
+
+/// Prefix system of byte sizes.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Units {
+    /// kB, MB, GB, ...
+    Si,
+    /// KiB, MiB, GiB, ...
+    #[default]
+    Binary,
+}
+
+impl Units {
+    fn base(self) -> f64 {
+        match self {
+            Units::Si => 1000.0,
+            Units::Binary => 1024.0,
+        }
+    }
+}
+
+const SI_SUFFIXES: [&str; 6] = ["B", "kB", "MB", "GB", "TB", "PB"];
+const BINARY_SUFFIXES: [&str; 6] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB"];
+
+/// A byte unit, e.g. MiB.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Unit {
+    units: Units,
+    /// 0 for bytes, 1 for kilo, 2 for mega, ...
+    power: usize,
+}
+
+impl Unit {
+    /// Parses the unit of a format specifier: `B`, `K` (or `k`), `M`, `G`,
+    /// `T`, `P` for SI units, `Ki`, `Mi`, `Gi`, `Ti`, `Pi` for binary ones.
+    pub fn parse(unit: &str) -> Option<Unit> {
+        let (prefix, units) = match unit.strip_suffix('i') {
+            Some(prefix) => (prefix, Units::Binary),
+            None => (unit, Units::Si),
+        };
+        let power = match prefix {
+            "B" if units == Units::Si => 0,
+            "k" | "K" => 1,
+            "M" => 2,
+            "G" => 3,
+            "T" => 4,
+            "P" => 5,
+            _ => return None,
+        };
+        Some(Unit { units, power })
+    }
+
+    /// The largest unit of `units` in which `bytes` is at least 1.
+    pub fn auto(bytes: f64, units: Units) -> Unit {
+        let mut unit = Unit { units, power: 0 };
+        while unit.power + 1 < SI_SUFFIXES.len() && bytes.abs() >= unit.factor() * units.base() {
+            unit.power += 1;
+        }
+        unit
+    }
+
+    pub fn factor(self) -> f64 {
+        self.units.base().powi(self.power as i32)
+    }
+
+    pub fn suffix(self) -> &'static str {
+        match self.units {
+            Units::Si => SI_SUFFIXES[self.power],
+            Units::Binary => BINARY_SUFFIXES[self.power],
+        }
+    }
+}
+
+/// Formats `bytes` in `unit` with `precision` decimals, e.g. `1.5GiB`. Plain
+/// bytes never get decimals.
+pub fn format_bytes(bytes: f64, unit: Unit, precision: usize) -> String {
+    let precision = match unit.power {
+        0 => 0,
+        _ => precision,
+    };
+    format!("{:.*}{}", precision, bytes / unit.factor(), unit.suffix())
+}